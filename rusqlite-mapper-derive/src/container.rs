@@ -0,0 +1,161 @@
+use darling::{ast::NestedMeta, util::PathList, Error, FromMeta};
+use heck::{
+    ToKebabCase, ToLowerCamelCase, ToShoutyKebabCase, ToShoutySnakeCase, ToSnakeCase,
+    ToUpperCamelCase,
+};
+use proc_macro2::{TokenStream, TokenTree};
+
+/// Struct level attributes shared by all derives, e.g. `#[rusqlite(table = "..")]`.
+#[derive(Debug, Default, FromMeta)]
+pub(crate) struct SqliteContainer {
+    /// Override the name of the table instead of using the snake cased struct name.
+    pub(crate) table: Option<String>,
+    /// The schema (e.g. an attached database) the table lives in.
+    pub(crate) schema: Option<String>,
+    /// Rename all columns according to the given rule. Like serde's `rename_all`.
+    pub(crate) rename_all: Option<RenameRule>,
+    /// Create the table as a `WITHOUT ROWID` table.
+    #[darling(default)]
+    pub(crate) without_rowid: bool,
+    /// Create the table as a `STRICT` table.
+    #[darling(default)]
+    pub(crate) strict: bool,
+    /// Table level `UNIQUE (..)` constraints over the listed fields.
+    #[darling(multiple)]
+    pub(crate) unique: Vec<PathList>,
+    /// Table level `CHECK (..)` constraints.
+    #[darling(multiple)]
+    pub(crate) check: Vec<String>,
+    /// Read the columns by position instead of by name, see `FromRow`.
+    #[darling(default)]
+    pub(crate) by_index: bool,
+    /// Indexes over one or more columns or expressions.
+    #[darling(multiple)]
+    pub(crate) index: Vec<Index>,
+}
+
+/// An index declared with `#[rusqlite(index(name = "..", columns(..), unique, where = ".."))]`.
+#[derive(Debug)]
+pub(crate) struct Index {
+    /// The name of the index, derived from the table and columns if not set.
+    pub(crate) name: Option<String>,
+    /// The indexed fields or sql expressions.
+    pub(crate) columns: IndexColumns,
+    /// Wether to create a `UNIQUE` index.
+    pub(crate) unique: bool,
+    /// The `WHERE` clause of a partial index.
+    pub(crate) where_clause: Option<String>,
+}
+
+/// The parsed list of `index(..)`, see [`Index`].
+#[derive(Debug, FromMeta)]
+struct IndexList {
+    name: Option<String>,
+    columns: IndexColumns,
+    #[darling(default)]
+    unique: bool,
+    where_clause: Option<String>,
+}
+
+impl FromMeta for Index {
+    fn from_meta(item: &syn::Meta) -> darling::Result<Self> {
+        let syn::Meta::List(list) = item else {
+            return Err(Error::unsupported_format("non-list").with_span(item));
+        };
+
+        // `where` is a keyword and can't be parsed as a meta item, so it is renamed first.
+        let tokens = list
+            .tokens
+            .clone()
+            .into_iter()
+            .map(|token| match token {
+                TokenTree::Ident(ident) if ident == "where" => {
+                    TokenTree::Ident(proc_macro2::Ident::new("where_clause", ident.span()))
+                }
+                token => token,
+            })
+            .collect::<TokenStream>();
+
+        let list = IndexList::from_list(&NestedMeta::parse_meta_list(tokens)?)?;
+
+        Ok(Self {
+            name: list.name,
+            columns: list.columns,
+            unique: list.unique,
+            where_clause: list.where_clause,
+        })
+    }
+}
+
+/// The entries of `columns(..)`: field names like `email` or sql expressions like `"lower(email)"`.
+#[derive(Debug)]
+pub(crate) struct IndexColumns(pub(crate) Vec<IndexColumn>);
+
+#[derive(Debug)]
+pub(crate) enum IndexColumn {
+    Field(syn::Path),
+    Expr(String),
+}
+
+impl FromMeta for IndexColumns {
+    fn from_list(items: &[NestedMeta]) -> darling::Result<Self> {
+        items
+            .iter()
+            .map(|item| match item {
+                NestedMeta::Meta(syn::Meta::Path(path)) => Ok(IndexColumn::Field(path.clone())),
+                NestedMeta::Lit(syn::Lit::Str(expr)) => Ok(IndexColumn::Expr(expr.value())),
+                _ => Err(
+                    Error::custom("expected a field name or an sql expression string")
+                        .with_span(item),
+                ),
+            })
+            .collect::<darling::Result<_>>()
+            .map(Self)
+    }
+}
+
+impl SqliteContainer {
+    /// Returns the name of the table, by default the snake cased struct name.
+    pub(crate) fn table_name(&self, ident: &syn::Ident) -> String {
+        self.table
+            .clone()
+            .unwrap_or_else(|| ident.to_string().to_snake_case())
+    }
+}
+
+/// A rule to rename all columns of a struct, see `#[rusqlite(rename_all = "..")]`.
+#[derive(Debug, Clone, Copy, FromMeta)]
+pub(crate) enum RenameRule {
+    #[darling(rename = "lowercase")]
+    Lower,
+    #[darling(rename = "UPPERCASE")]
+    Upper,
+    #[darling(rename = "PascalCase")]
+    Pascal,
+    #[darling(rename = "camelCase")]
+    Camel,
+    #[darling(rename = "snake_case")]
+    Snake,
+    #[darling(rename = "SCREAMING_SNAKE_CASE")]
+    ScreamingSnake,
+    #[darling(rename = "kebab-case")]
+    Kebab,
+    #[darling(rename = "SCREAMING-KEBAB-CASE")]
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    /// Applies this rule to a rust field name.
+    pub(crate) fn apply(self, name: &str) -> String {
+        match self {
+            RenameRule::Lower => name.to_lowercase(),
+            RenameRule::Upper => name.to_uppercase(),
+            RenameRule::Pascal => name.to_upper_camel_case(),
+            RenameRule::Camel => name.to_lower_camel_case(),
+            RenameRule::Snake => name.to_snake_case(),
+            RenameRule::ScreamingSnake => name.to_shouty_snake_case(),
+            RenameRule::Kebab => name.to_kebab_case(),
+            RenameRule::ScreamingKebab => name.to_shouty_kebab_case(),
+        }
+    }
+}
@@ -2,9 +2,9 @@ use darling::{ast::Data, Error, FromDeriveInput};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens};
-use syn::{DeriveInput, Result};
+use syn::{ext::IdentExt, DeriveInput, Result};
 
-use crate::fields::SqliteField;
+use crate::{container::SqliteContainer, fields::SqliteField};
 
 /// Fallible entry point for generating a `FromRow`, `ToRow` implementation
 pub(crate) fn try_derive(input: &DeriveInput) -> std::result::Result<TokenStream, Error> {
@@ -23,6 +23,8 @@ pub(crate) struct DeriveFromRow {
     pub(crate) ident: syn::Ident,
     pub(crate) generics: syn::Generics,
     pub(crate) data: Data<(), SqliteField>,
+    #[darling(flatten)]
+    pub(crate) container: SqliteContainer,
 }
 
 impl DeriveFromRow {
@@ -30,6 +32,14 @@ impl DeriveFromRow {
     fn validate(&self) -> Result<()> {
         for field in self.fields() {
             field.validate()?;
+            field.validate_conversions()?;
+
+            if self.container.by_index && field.default_if_missing.is_some() {
+                return Err(Error::custom(
+                    "can't combine `#[rusqlite(default_if_missing)]` with `#[rusqlite(by_index)]`",
+                )
+                .into());
+            }
         }
 
         Ok(())
@@ -72,27 +82,133 @@ impl DeriveFromRow {
         let ident = &self.ident;
 
         let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
-        let original_predicates = where_clause.map(|w| &w.predicates).into_iter();
+        let original_predicates = where_clause.into_iter().flat_map(|w| &w.predicates);
         let predicates = self.predicates()?;
 
-        let is_all_null_fields = self
+        let resolve_fields = self
+            .fields()
+            .iter()
+            .map(|f| f.generate_resolve_columns(&self.container))
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        // Positions set with `position = N` are relative to the first column of this struct.
+        let has_positions = self.fields().iter().any(|f| f.position.is_some());
+        let resolve_base = (has_positions || self.container.by_index)
+            .then(|| quote!(let base = resolver.position();));
+
+        // Columns after a struct mapped `by_index` start at the end of its span, even if
+        // its last field went back with `position = N`.
+        let resolve_end = self
+            .container
+            .by_index
+            .then(|| quote!(resolver.seek(base + Self::COLUMN_COUNT);));
+
+        let columns_fields = self
             .fields()
             .iter()
-            .map(|f| f.generate_is_all_null())
+            .map(|f| f.generate_columns(&self.container))
             .collect::<syn::Result<Vec<_>>>()?;
 
+        let column_widths = self
+            .fields()
+            .iter()
+            .map(|f| f.generate_column_count())
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        // The width of the span from the first column to the last one read. Fields with
+        // `position = N` start at `N`, the others after the previous field.
+        let column_count = if has_positions {
+            let seeks = self
+                .fields()
+                .iter()
+                .map(|f| f.position.map(|position| quote!(position = #position;)))
+                .collect::<Vec<_>>();
+            quote! {{
+                let mut position = 0;
+                let mut end = 0;
+                #(
+                    #seeks
+                    position += #column_widths;
+                    if position > end {
+                        end = position;
+                    }
+                )*
+                end
+            }}
+        } else {
+            quote!(0 #(+ #column_widths)*)
+        };
+
+        // Flattened types reading by position, like `Scalar`, need the plan as well.
+        let by_position = if resolve_base.is_some() {
+            quote!(true)
+        } else {
+            let flattened = self
+                .fields()
+                .iter()
+                .filter(|f| f.flatten)
+                .map(|f| {
+                    let target_ty = f.target_ty()?;
+                    Ok(quote!(<#target_ty as rusqlite_mapper::FromRow>::BY_POSITION))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(false #(|| #flattened)*)
+        };
+
         let try_from_row_fields = self
             .all_fields()
             .iter()
-            .map(|f| f.generate_try_from_row())
+            .map(|f| f.generate_try_from_row(&ident.to_string(), &self.container, true))
             .collect::<syn::Result<Vec<_>>>()?;
 
-        Ok(quote! {
-            impl #impl_generics rusqlite_mapper::FromRow for #ident #ty_generics where #(#original_predicates),* #(#predicates),* {
+        let is_all_null_fields = self
+            .fields()
+            .iter()
+            .map(|f| f.generate_is_all_null(&self.container, true))
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        // Single rows look up their columns by name directly instead of building a `RowPlan`.
+        // Columns read by position need the plan.
+        let by_name = if resolve_base.is_some() {
+            quote! {
                 fn try_from_row_prefixed(
                     row: &::rusqlite::Row,
                     prefix: Option<&str>
                 ) -> std::result::Result<Self, ::rusqlite::Error> {
+                    rusqlite_mapper::RowPlan::<Self>::prefixed(::std::convert::AsRef::as_ref(row), prefix)?
+                        .try_from_row(row)
+                }
+
+                fn is_all_null(
+                    row: &::rusqlite::Row,
+                    prefix: Option<&str>
+                ) -> std::result::Result<bool, ::rusqlite::Error> {
+                    rusqlite_mapper::RowPlan::<Self>::prefixed(::std::convert::AsRef::as_ref(row), prefix)?
+                        .is_all_null(row)
+                }
+            }
+        } else {
+            let try_from_row_fields = self
+                .all_fields()
+                .iter()
+                .map(|f| f.generate_try_from_row(&ident.to_string(), &self.container, false))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            let is_all_null_fields = self
+                .fields()
+                .iter()
+                .map(|f| f.generate_is_all_null(&self.container, false))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            quote! {
+                fn try_from_row_prefixed(
+                    row: &::rusqlite::Row,
+                    prefix: Option<&str>
+                ) -> std::result::Result<Self, ::rusqlite::Error> {
+                    if Self::BY_POSITION {
+                        return rusqlite_mapper::RowPlan::<Self>::prefixed(::std::convert::AsRef::as_ref(row), prefix)?
+                            .try_from_row(row);
+                    }
                     Ok(Self {
                         #(#try_from_row_fields),*
                     })
@@ -102,8 +218,55 @@ impl DeriveFromRow {
                     row: &::rusqlite::Row,
                     prefix: Option<&str>
                 ) -> std::result::Result<bool, ::rusqlite::Error> {
-                    Ok(#(#is_all_null_fields)&&*)
+                    if Self::BY_POSITION {
+                        return rusqlite_mapper::RowPlan::<Self>::prefixed(::std::convert::AsRef::as_ref(row), prefix)?
+                            .is_all_null(row);
+                    }
+                    Ok(true #(&& #is_all_null_fields)*)
+                }
+            }
+        };
+
+        Ok(quote! {
+            impl #impl_generics rusqlite_mapper::FromRow for #ident #ty_generics where #(#original_predicates,)* #(#predicates),* {
+                const COLUMN_COUNT: usize = #column_count;
+                const BY_POSITION: bool = #by_position;
+
+                fn columns(prefix: Option<&str>) -> Vec<String> {
+                    #[allow(unused_mut)]
+                    let mut columns = Vec::with_capacity(Self::COLUMN_COUNT);
+                    #(#columns_fields)*
+                    columns
+                }
+
+                fn resolve_columns(
+                    resolver: &mut rusqlite_mapper::ColumnResolver<'_>,
+                    prefix: Option<&str>
+                ) -> std::result::Result<(), ::rusqlite::Error> {
+                    #resolve_base
+                    #(#resolve_fields)*
+                    #resolve_end
+                    Ok(())
+                }
+
+                fn try_from_row_indexed(
+                    row: &::rusqlite::Row,
+                    cursor: &mut rusqlite_mapper::ColumnCursor<'_>
+                ) -> std::result::Result<Self, ::rusqlite::Error> {
+                    Ok(Self {
+                        #(#try_from_row_fields),*
+                    })
+                }
+
+                fn is_all_null_indexed(
+                    row: &::rusqlite::Row,
+                    cursor: &mut rusqlite_mapper::ColumnCursor<'_>
+                ) -> std::result::Result<bool, ::rusqlite::Error> {
+                    // Not short circuiting, the cursor has to advance past all columns.
+                    Ok(true #(& #is_all_null_fields)*)
                 }
+
+                #by_name
             }
         }
         .into())
@@ -127,6 +290,36 @@ impl SqliteField {
             .into());
         }
 
+        if self.flatten && (self.default_value.is_some() || self.default_if_missing.is_some()) {
+            return Err(Error::custom(
+                "can't combine `#[rusqlite(flatten)]` with `#[rusqlite(default_value)]` or `#[rusqlite(default_if_missing)]`",
+            )
+            .into());
+        }
+
+        if self.deserialize_with().is_some()
+            && (self.flatten || self.from.is_some() || self.try_from.is_some())
+        {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(deserialize_with = "..")]` or `#[rusqlite(with = "..")]` with `#[rusqlite(flatten)]`, `#[rusqlite(from = "..")]` or `#[rusqlite(try_from = "..")]`"#,
+            )
+            .into());
+        }
+
+        if self.position.is_some() && self.default_if_missing.is_some() {
+            return Err(Error::custom(
+                "can't combine `#[rusqlite(position = N)]` with `#[rusqlite(default_if_missing)]`",
+            )
+            .into());
+        }
+
+        if self.rename.is_some() && self.position.is_some() {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(position = N)]` with `#[rusqlite(rename = "..")]`"#,
+            )
+            .into());
+        }
+
         Ok(())
     }
 
@@ -142,24 +335,112 @@ impl SqliteField {
         }
     }
 
-    fn generate_is_all_null(&self) -> Result<TokenStream2> {
-        let column_name = self.column_name();
-        let target_ty = self.target_ty()?;
-
+    /// Generate the statement resolving the columns of this field when calling `resolve_columns`.
+    ///
+    /// Columns are looked up by name, unless the field has a `position = N` or the struct is
+    /// mapped `by_index`, then the column at position `N` or the next column is used.
+    /// Flattened fields resolve their columns themselves, starting at position `N` if set.
+    fn generate_resolve_columns(&self, container: &SqliteContainer) -> Result<TokenStream2> {
         let line = if self.flatten {
-            let prefix = if let Some(prefix) = &self.prefix {
-                quote!(Some(&(prefix.unwrap_or("").to_string() + #prefix)))
-            } else {
-                quote!(prefix)
-            };
+            let target_ty = self.target_ty()?;
+            let prefix = self.flatten_prefix();
+            let resolve = quote!(<#target_ty as rusqlite_mapper::FromRow>::resolve_columns(resolver, #prefix)?;);
+
+            match self.position {
+                // The following fields continue after the furthest column, not after this one.
+                Some(position) => quote! {
+                    let end = resolver.position();
+                    resolver.seek(base + #position);
+                    #resolve
+                    resolver.seek(end.max(resolver.position()));
+                },
+                None => resolve,
+            }
+        } else if let Some(position) = self.position {
+            quote!(resolver.resolve_index(base + #position)?;)
+        } else if container.by_index {
+            quote!(resolver.resolve_next()?;)
+        } else if self.default_if_missing.is_some() {
+            let column_name = self.column_name(container);
+            quote!(resolver.resolve_optional(prefix, #column_name);)
+        } else {
+            let column_name = self.column_name(container);
+            quote!(resolver.resolve(prefix, #column_name)?;)
+        };
 
+        Ok(line)
+    }
+
+    /// The prefix passed to the `FromRow` implementation of a flattened field: the prefix of
+    /// the struct joined with the `prefix` of the field.
+    fn flatten_prefix(&self) -> TokenStream2 {
+        if let Some(prefix) = &self.prefix {
+            quote!(Some(&(prefix.unwrap_or("").to_string() + #prefix)))
+        } else {
+            quote!(prefix)
+        }
+    }
+
+    /// Generate the statement adding the columns of this field when calling `columns`.
+    fn generate_columns(&self, container: &SqliteContainer) -> Result<TokenStream2> {
+        if self.flatten {
+            let target_ty = self.target_ty()?;
+            let prefix = self.flatten_prefix();
+            Ok(quote!(columns.extend(<#target_ty as rusqlite_mapper::FromRow>::columns(#prefix));))
+        } else {
+            let column_name = self.column_name(container);
+            Ok(quote!(columns.push(prefix.unwrap_or("").to_string() + #column_name);))
+        }
+    }
+
+    /// Generate the width of the columns read by this field, used for `COLUMN_COUNT`.
+    fn generate_column_count(&self) -> Result<TokenStream2> {
+        if self.flatten {
+            let target_ty = self.target_ty()?;
+            Ok(quote!(<#target_ty as rusqlite_mapper::FromRow>::COLUMN_COUNT))
+        } else {
+            Ok(quote!(1))
+        }
+    }
+
+    /// Generate the expression looking up the index of this field's column, from the
+    /// `cursor` of a plan if `indexed`, else by name. It is an `Option` for fields with
+    /// `default_if_missing`.
+    fn generate_index(&self, container: &SqliteContainer, indexed: bool) -> TokenStream2 {
+        let column_name = self.column_name(container);
+        match (indexed, self.default_if_missing.is_some()) {
+            (true, true) => quote!(cursor.next_optional_index()),
+            (true, false) => quote!(cursor.next_index()),
+            (false, true) => quote!(rusqlite_mapper::find_column(row, prefix, #column_name)),
+            (false, false) => quote!(rusqlite_mapper::column_index(row, prefix, #column_name)?),
+        }
+    }
+
+    /// Generate the expression checking this field for sql `null` when calling
+    /// `is_all_null_indexed`, or `is_all_null` if not `indexed`.
+    fn generate_is_all_null(
+        &self,
+        container: &SqliteContainer,
+        indexed: bool,
+    ) -> Result<TokenStream2> {
+        let target_ty = self.target_ty()?;
+        let index = self.generate_index(container, indexed);
+
+        let line = if self.flatten && indexed {
+            quote!(<#target_ty as rusqlite_mapper::FromRow>::is_all_null_indexed(row, cursor)?)
+        } else if self.flatten {
+            let prefix = self.flatten_prefix();
             quote!(<#target_ty as rusqlite_mapper::FromRow>::is_all_null(row, #prefix)?)
+        } else if self.default_if_missing.is_some() {
+            quote! {
+                (match #index {
+                    Some(index) => ::rusqlite::Row::get_ref(row, index)? == ::rusqlite::types::ValueRef::Null,
+                    None => true,
+                })
+            }
         } else {
             quote! {
-                ::rusqlite::Row::get_ref::<&str>(
-                    row,
-                    &(prefix.unwrap_or("").to_string() + #column_name)
-                )? == ::rusqlite::types::ValueRef::Null
+                (::rusqlite::Row::get_ref(row, #index)? == ::rusqlite::types::ValueRef::Null)
             }
         };
 
@@ -168,7 +449,7 @@ impl SqliteField {
 
     /// Pushes the needed where clause predicates for this field.
     ///
-    /// By default this is `T: rusqlite::types::FromSql`,
+    /// By default this is `T: rusqlite::types::FromSql`, none when using `deserialize_with`,
     /// when using `flatten` it's: `T: rusqlite_mapper::FromRow`
     /// and when using either `from` or `try_from` attributes it additionally pushes this bound:
     /// `T: std::convert::From<R>`, where `T` is the type specified in the struct and `R` is the
@@ -177,11 +458,11 @@ impl SqliteField {
         let target_ty = &self.target_ty()?;
         let ty = &self.ty;
 
-        predicates.push(if self.flatten {
-            quote! (#target_ty: rusqlite_mapper::FromRow)
-        } else {
-            quote! (#target_ty: ::rusqlite::types::FromSql)
-        });
+        if self.flatten {
+            predicates.push(quote! (#target_ty: rusqlite_mapper::FromRow));
+        } else if self.deserialize_with().is_none() {
+            predicates.push(quote! (#target_ty: ::rusqlite::types::FromSql));
+        }
 
         if self.from.is_some() {
             predicates.push(quote!(#ty: std::convert::From<#target_ty>))
@@ -197,36 +478,105 @@ impl SqliteField {
         Ok(())
     }
 
-    /// Generate the line needed to retrieve this field from a row when calling `try_from_row`.
-    fn generate_try_from_row(&self) -> Result<TokenStream2> {
+    /// Generate the line needed to retrieve this field from a row when calling
+    /// `try_from_row_indexed`, or `try_from_row_prefixed` if not `indexed`.
+    ///
+    /// Errors are wrapped in a `MapperError` naming the struct `struct_name` and this field.
+    fn generate_try_from_row(
+        &self,
+        struct_name: &str,
+        container: &SqliteContainer,
+        indexed: bool,
+    ) -> Result<TokenStream2> {
         let ident = self.ident.as_ref().unwrap();
 
         if self.skip.is_some() {
             return Ok(quote!(#ident: Default::default()));
         }
 
-        let column_name = self.column_name();
+        let field_name = ident.unraw().to_string();
         let field_ty = &self.ty;
         let target_ty = self.target_ty()?;
 
-        let mut base = if self.flatten {
-            let prefix = if let Some(prefix) = &self.prefix {
-                quote!(Some(&(prefix.unwrap_or("").to_string() + #prefix)))
+        let convert = if self.from.is_some() {
+            quote!(.map(<#field_ty as std::convert::From<#target_ty>>::from))
+        } else if self.try_from.is_some() {
+            quote! {
+                .and_then(|value| {
+                    <#field_ty as std::convert::TryFrom<#target_ty>>::try_from(value)
+                        .map_err(::rusqlite::Error::from)
+                })
+            }
+        } else {
+            quote!()
+        };
+
+        let value = if self.flatten {
+            let read = if indexed {
+                quote!(<#target_ty as rusqlite_mapper::FromRow>::try_from_row_indexed(row, cursor))
             } else {
-                quote!(prefix)
+                let prefix = self.flatten_prefix();
+                quote!(<#target_ty as rusqlite_mapper::FromRow>::try_from_row_prefixed(row, #prefix))
             };
-
-            quote!(<#target_ty as rusqlite_mapper::FromRow>::try_from_row_prefixed(row, #prefix)?)
+            quote! {
+                #read
+                    #convert
+                    .map_err(|err| rusqlite_mapper::MapperError::nested(err, #struct_name, #field_name))?
+            }
         } else {
-            quote!(::rusqlite::Row::get::<&str, #target_ty>(row, &(prefix.unwrap_or("").to_string() + #column_name))?)
-        };
+            let mut read = quote!(::rusqlite::Row::get::<usize, #target_ty>(row, index) #convert);
+            if let Some(deserialize) = self.deserialize_with() {
+                read = if self.default_value.is_some() {
+                    let default_value = self.default_expr();
+                    quote! {
+                        rusqlite_mapper::read_with(row, index, |value| match value {
+                            ::rusqlite::types::ValueRef::Null => Ok(#default_value),
+                            value => #deserialize(value),
+                        })
+                    }
+                } else {
+                    quote!(rusqlite_mapper::read_with(row, index, #deserialize))
+                };
+            } else if self.default_value.is_some() {
+                let default_value = self.default_expr();
+                read = quote! {
+                    ::rusqlite::Row::get::<usize, Option<#target_ty>>(row, index).and_then(|value| match value {
+                        Some(value) => Ok::<#target_ty, ::rusqlite::Error>(value) #convert,
+                        None => Ok(#default_value),
+                    })
+                };
+            }
 
-        if self.from.is_some() {
-            base = quote!(<#field_ty as std::convert::From<#target_ty>>::from(#base));
-        } else if self.try_from.is_some() {
-            base = quote!(<#field_ty as std::convert::TryFrom<#target_ty>>::try_from(#base)?);
+            let read = quote! {
+                #read.map_err(|err| {
+                    rusqlite_mapper::MapperError::column(
+                        err,
+                        row,
+                        index,
+                        #struct_name,
+                        #field_name,
+                        std::any::type_name::<#field_ty>(),
+                    )
+                })?
+            };
+
+            let index = self.generate_index(container, indexed);
+            if self.default_if_missing.is_some() {
+                let default_value = self.default_expr();
+                quote! {
+                    match #index {
+                        Some(index) => #read,
+                        None => #default_value,
+                    }
+                }
+            } else {
+                quote! {{
+                    let index = #index;
+                    #read
+                }}
+            }
         };
 
-        Ok(quote!(#ident: #base))
+        Ok(quote!(#ident: #value))
     }
 }
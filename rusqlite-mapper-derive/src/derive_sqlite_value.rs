@@ -44,9 +44,7 @@ impl DeriveSqliteValue {
 
         Ok(quote! {
             impl rusqlite_mapper::SqliteTypeInfo for #ident {
-                fn sqlite_type() -> &'static str {
-                    "TEXT"
-                }
+                const SQLITE_TYPE: &'static str = "TEXT";
             }
 
             impl rusqlite::ToSql for #ident {
@@ -75,9 +73,7 @@ impl DeriveSqliteValue {
 
         Ok(quote! {
             impl rusqlite_mapper::SqliteTypeInfo for #ident {
-                fn sqlite_type() -> &'static str {
-                    "TEXT"
-                }
+                const SQLITE_TYPE: &'static str = "TEXT";
             }
 
             impl rusqlite::ToSql for #ident {
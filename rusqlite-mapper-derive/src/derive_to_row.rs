@@ -1,11 +1,13 @@
-use darling::{ast::Data, Error, FromDeriveInput};
-use heck::ToSnakeCase;
+use darling::{ast::Data, util::PathList, Error, FromDeriveInput};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{quote, ToTokens};
-use syn::{DeriveInput, Result};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{spanned::Spanned, DeriveInput, Result};
 
-use crate::fields::SqliteField;
+use crate::{
+    container::{IndexColumn, SqliteContainer},
+    fields::{doc_comment, ForeignKeyAction, ReferenceTarget, References, SqliteField},
+};
 
 /// Fallible entry point for generating a `FromRow`, `ToRow` implementation
 pub(crate) fn try_derive(input: &DeriveInput) -> std::result::Result<TokenStream, Error> {
@@ -24,6 +26,9 @@ pub(crate) struct DeriveToRow {
     pub(crate) ident: syn::Ident,
     pub(crate) generics: syn::Generics,
     pub(crate) data: Data<(), SqliteField>,
+    pub(crate) attrs: Vec<syn::Attribute>,
+    #[darling(flatten)]
+    pub(crate) container: SqliteContainer,
 }
 
 impl DeriveToRow {
@@ -39,58 +44,275 @@ impl DeriveToRow {
         }
     }
 
-    /// Generate the `FromRow` implementation.
+    /// Resolves a field referenced in a struct level attribute to its column name.
+    fn field_column(&self, path: &syn::Path) -> Result<String> {
+        self.fields()
+            .into_iter()
+            .find(|field| path.is_ident(field.ident.as_ref().unwrap()))
+            .map(|field| field.column_name(&self.container))
+            .ok_or_else(|| {
+                Error::custom(format!("unknown field `{}`", path.to_token_stream()))
+                    .with_span(path)
+                    .into()
+            })
+    }
+
+    /// Resolves the fields listed in a table level constraint to their column names.
+    fn constraint_columns(&self, fields: &PathList) -> Result<Vec<String>> {
+        fields.iter().map(|path| self.field_column(path)).collect()
+    }
+
+    /// Generates the `IndexDef`s of the field level `index` / `unique_index` and the struct
+    /// level `index(..)` attributes.
+    fn indexes(&self) -> Result<Vec<TokenStream2>> {
+        let table_name = self.container.table_name(&self.ident);
+        let mut indexes = Vec::new();
+
+        for field in self.fields() {
+            if field.index.is_none() && field.unique_index.is_none() {
+                continue;
+            }
+            let column = field.column_name(&self.container);
+            let name = format!("idx_{table_name}_{column}");
+            let unique = field.unique_index.is_some();
+            indexes.push(quote! {
+                rusqlite_mapper::IndexDef {
+                    name: #name,
+                    columns: &[rusqlite_mapper::IndexColumn::Column(#column)],
+                    unique: #unique,
+                    where_clause: None,
+                }
+            });
+        }
+
+        for index in &self.container.index {
+            let mut names = vec![table_name.clone()];
+            let mut columns = Vec::new();
+            for column in &index.columns.0 {
+                match column {
+                    IndexColumn::Field(path) => {
+                        let column = self.field_column(path)?;
+                        names.push(column.clone());
+                        columns.push(quote!(rusqlite_mapper::IndexColumn::Column(#column)));
+                    }
+                    IndexColumn::Expr(expr) => {
+                        columns.push(quote!(rusqlite_mapper::IndexColumn::Expr(#expr)));
+                    }
+                }
+            }
+            let name = match &index.name {
+                Some(name) => name.clone(),
+                None if names.len() == columns.len() + 1 => format!("idx_{}", names.join("_")),
+                None => {
+                    return Err(
+                        Error::custom(r#"indexes on expressions need a `name = ".."`"#)
+                            .with_span(&self.ident)
+                            .into(),
+                    )
+                }
+            };
+            let unique = index.unique;
+            let where_clause = quote_option(index.where_clause.as_deref());
+            indexes.push(quote! {
+                rusqlite_mapper::IndexDef {
+                    name: #name,
+                    columns: &[#(#columns),*],
+                    unique: #unique,
+                    where_clause: #where_clause,
+                }
+            });
+        }
+
+        Ok(indexes)
+    }
+
+    /// Generates any additional where clause predicates needed for the fields in this struct.
+    pub(crate) fn predicates(&self) -> Result<Vec<TokenStream2>> {
+        let mut predicates = Vec::new();
+
+        for field in self.fields() {
+            field.add_to_row_predicates(&mut predicates)?;
+        }
+
+        Ok(predicates)
+    }
+
+    /// Validates the struct level attributes.
+    fn validate(&self) -> Result<()> {
+        let primary_keys = self.fields().iter().filter(|f| f.is_primary_key()).count();
+
+        for field in self.fields() {
+            if field.autoincrement.is_some() && (!field.is_primary_key() || primary_keys > 1) {
+                return Err(Error::custom(
+                    r#"`#[rusqlite(autoincrement)]` requires a single `#[rusqlite(primary_key)]` field"#,
+                )
+                .with_span(&field.ident)
+                .into());
+            }
+        }
+
+        for field in self.fields() {
+            field.validate_conversions()?;
+
+            if field.auto.is_some() && (!field.is_primary_key() || primary_keys > 1) {
+                return Err(Error::custom(
+                    r#"`#[rusqlite(auto)]` requires a single `#[rusqlite(primary_key)]` field"#,
+                )
+                .with_span(&field.ident)
+                .into());
+            }
+        }
+
+        if self.container.without_rowid
+            && self
+                .fields()
+                .iter()
+                .any(|f| f.autoincrement.is_some() || f.auto.is_some())
+        {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(without_rowid)]` with `#[rusqlite(autoincrement)]` or `#[rusqlite(auto)]`"#,
+            )
+            .with_span(&self.ident)
+            .into());
+        }
+
+        if self.container.without_rowid && primary_keys == 0 {
+            return Err(Error::custom(
+                r#"`#[rusqlite(without_rowid)]` requires at least one `#[rusqlite(primary_key)]` field"#,
+            )
+            .with_span(&self.ident)
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Generate the `ToRow` implementation.
     fn generate(self) -> Result<TokenStream> {
+        self.validate()?;
+
         let ident = &self.ident;
 
-        let (impl_generics, ty_generics, _where_clause) = self.generics.split_for_impl();
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+        let original_predicates = where_clause.into_iter().flat_map(|w| &w.predicates);
+        let predicates = self.predicates()?;
 
-        let params = self
+        let insert_fields = self
             .fields()
+            .into_iter()
+            .filter(|field| field.auto.is_none() && field.skip_insert.is_none())
+            .collect::<Vec<_>>();
+
+        // Without conversions the fields are bound as `&dyn ToSql`, no need for `Param`.
+        let converts = self.fields().iter().any(|field| field.converts());
+        let param = if converts {
+            quote!(rusqlite_mapper::Param<'__params>)
+        } else {
+            quote!(&'__params dyn ::rusqlite::ToSql)
+        };
+
+        let param_values = insert_fields
             .iter()
-            .map(|field| {
-                let ty = field.param_ty()?;
-                Ok(quote! { #ty })
-            })
+            .map(|field| field.param_ref(converts))
             .collect::<Result<Vec<_>>>()?;
 
-        let param_values = self
+        let param_count = param_values.len();
+
+        let (key_fields, value_fields): (Vec<_>, Vec<_>) = self
             .fields()
+            .into_iter()
+            .partition(|field| field.is_primary_key());
+
+        let pk_values = key_fields
             .iter()
-            .map(|field| field.param_ref())
+            .map(|field| field.param_ref(converts))
             .collect::<Result<Vec<_>>>()?;
+        let pk_count = pk_values.len();
+
+        let update_values = value_fields
+            .iter()
+            .map(|field| field.param_ref(converts))
+            .chain(pk_values.iter().cloned().map(Ok))
+            .collect::<Result<Vec<_>>>()?;
+        let update_count = update_values.len();
 
         let column_names = self
             .fields()
             .iter()
-            .map(|field| field.column_name())
+            .map(|field| field.column_name(&self.container))
             .collect::<Vec<_>>();
 
-        let table_name = self.ident.to_string().to_snake_case();
-
-        let sql_types = self
-            .fields()
+        let insert_column_names = insert_fields
             .iter()
-            .map(|field| {
+            .map(|field| field.column_name(&self.container))
+            .collect::<Vec<_>>();
+
+        let set_rowid = match self.fields().into_iter().find(|field| field.auto.is_some()) {
+            Some(field) => {
+                let ident = field.ident.as_ref().unwrap();
                 let ty = &field.ty;
-                let primary_key = field.is_primary_key();
-                quote! { (
-                    <#ty as rusqlite_mapper::SqliteTypeInfo>::sqlite_type().to_string(),
-                    <#ty as rusqlite_mapper::SqliteTypeInfo>::optional(),
-                    #primary_key,
-                ) }
+                quote_spanned! {ty.span()=>
+                    fn set_rowid(&mut self, rowid: i64) -> ::rusqlite::Result<()> {
+                        self.#ident = <#ty as rusqlite_mapper::FromRowId>::from_row_id(rowid)?;
+                        Ok(())
+                    }
+                }
+            }
+            None => quote!(),
+        };
+
+        let table_name = self.container.table_name(&self.ident);
+        let schema_name = quote_option(self.container.schema.as_deref());
+        let without_rowid = self.container.without_rowid;
+        let strict = self.container.strict;
+        let doc = quote_option(doc_comment(&self.attrs).as_deref());
+        let unique = self
+            .container
+            .unique
+            .iter()
+            .map(|fields| {
+                let columns = self.constraint_columns(fields)?;
+                Ok(quote!(&[#(#columns),*]))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>>>()?;
+        let checks = &self.container.check;
+        let indexes = self.indexes()?;
+
+        let column_defs = self
+            .fields()
+            .iter()
+            .map(|field| field.column_def(&self.ident, &self.container))
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(quote! {
-            impl #impl_generics rusqlite_mapper::ToRow for #ident #ty_generics {
-                type Params<'a> = (#(#params),*,)
+            impl #impl_generics rusqlite_mapper::ToRow for #ident #ty_generics where #(#original_predicates,)* #(#predicates),* {
+                type Params<'__params> = ::rusqlite::ParamsFromIter<[#param; #param_count]>
                 where
-                    Self: 'a;
+                    Self: '__params;
 
-                fn table_name() -> &'static str {
-                    #table_name
-                }
+                type UpdateParams<'__params> = ::rusqlite::ParamsFromIter<[#param; #update_count]>
+                where
+                    Self: '__params;
+
+                type PkParams<'__params> = ::rusqlite::ParamsFromIter<[#param; #pk_count]>
+                where
+                    Self: '__params;
+
+                const TABLE: &'static rusqlite_mapper::TableDef = &rusqlite_mapper::TableDef {
+                    name: #table_name,
+                    schema: #schema_name,
+                    columns: &[
+                        #(
+                            #column_defs,
+                        )*
+                    ],
+                    without_rowid: #without_rowid,
+                    strict: #strict,
+                    unique: &[#(#unique),*],
+                    checks: &[#(#checks),*],
+                    indexes: &[#(#indexes),*],
+                    doc: #doc,
+                };
 
                 fn column_names() -> &'static [&'static str] {
                     &[
@@ -100,17 +322,40 @@ impl DeriveToRow {
                     ]
                 }
 
+                fn insert_column_names() -> &'static [&'static str] {
+                    &[
+                        #(
+                            #insert_column_names,
+                        )*
+                    ]
+                }
+
+                #set_rowid
+
                 fn to_params(&self) -> Self::Params<'_> {
-                    (#(#param_values),*,)
+                    ::rusqlite::params_from_iter([
+                        #(
+                            #param_values,
+                        )*
+                    ])
                 }
 
-                fn sql_types() -> Vec<(String, bool, bool)> {
-                    vec![
+                fn to_update_params(&self) -> Self::UpdateParams<'_> {
+                    ::rusqlite::params_from_iter([
                         #(
-                            #sql_types,
+                            #update_values,
                         )*
-                    ]
+                    ])
                 }
+
+                fn pk_params(&self) -> Self::PkParams<'_> {
+                    ::rusqlite::params_from_iter([
+                        #(
+                            #pk_values,
+                        )*
+                    ])
+                }
+
             }
         }
         .into())
@@ -118,112 +363,197 @@ impl DeriveToRow {
 }
 
 impl SqliteField {
-    /// The Rust type of when this field is converted to a param
-    fn param_ty(&self) -> Result<TokenStream2> {
-        type_to_param_ty(&self.ty, self.value.is_some())
+    /// The `ColumnDef` describing this field.
+    fn column_def(
+        &self,
+        self_ident: &syn::Ident,
+        container: &SqliteContainer,
+    ) -> Result<TokenStream2> {
+        let ty = &self.ty;
+        let column_ty = self.column_ty()?;
+        let name = self.column_name(container);
+        let sql_type = match (&self.sql_type, &self.with) {
+            (Some(sql_type), _) => quote!(#sql_type),
+            (None, Some(with)) => quote!(#with::sqlite_type()),
+            (None, None) => quote!(<#column_ty as rusqlite_mapper::SqliteTypeInfo>::SQLITE_TYPE),
+        };
+        let nullable = if self.has_type_info() {
+            quote!(<#column_ty as rusqlite_mapper::SqliteTypeInfo>::OPTIONAL)
+        } else {
+            let optional = self.is_option();
+            quote!(#optional)
+        };
+        let primary_key = self.is_primary_key();
+        let autoincrement = self.autoincrement.is_some();
+        let default = quote_option(self.default.as_deref());
+        let unique = self.unique.is_some();
+        let collate = quote_option(self.collate.as_deref());
+        let check = quote_option(self.check.as_deref());
+        let references = match &self.references {
+            Some(references) => {
+                let foreign_key = references.foreign_key(self_ident, container);
+                quote!(Some(#foreign_key))
+            }
+            None => quote!(None),
+        };
+        let doc = quote_option(doc_comment(&self.attrs).as_deref());
+
+        Ok(quote_spanned! {ty.span()=>
+            rusqlite_mapper::ColumnDef {
+                name: #name,
+                sql_type: #sql_type,
+                nullable: #nullable,
+                primary_key: #primary_key,
+                autoincrement: #autoincrement,
+                default: #default,
+                unique: #unique,
+                collate: #collate,
+                references: #references,
+                check: #check,
+                doc: #doc,
+            }
+        })
     }
 
-    /// The Rust expression to access this field as a param
-    fn param_ref(&self) -> Result<TokenStream2> {
+    /// Pushes the where clause predicates needed to use this field as a param:
+    /// `T: rusqlite::ToSql` and `T: rusqlite_mapper::SqliteTypeInfo`.
+    ///
+    /// With `into` or `try_into` the target type `R` needs `ToSql` instead, and the field
+    /// `T: Clone + Into<R>` or `T: Clone + TryInto<R>`. `SqliteTypeInfo` is needed by the
+    /// type stored in the column, see `SqliteField::column_ty`. Fields with `serialize_with`
+    /// don't need `ToSql`, and with `with` or `sql_type` they don't need `SqliteTypeInfo`
+    /// either.
+    fn add_to_row_predicates(&self, predicates: &mut Vec<TokenStream2>) -> Result<()> {
+        let ty = &self.ty;
+        let column_ty = self.column_ty()?;
+
+        // Spanned to the field type so a missing impl is reported at the field.
+        if let Some(into_ty) = self.param_ty()? {
+            if self.into.is_some() {
+                predicates.push(quote_spanned! {ty.span()=>
+                    #ty: std::clone::Clone + std::convert::Into<#into_ty>
+                });
+            } else {
+                let try_into = quote_spanned!(ty.span()=> std::convert::TryInto<#into_ty>);
+                predicates.push(quote_spanned!(ty.span()=> #ty: std::clone::Clone + #try_into));
+                predicates.push(quote_spanned! {ty.span()=>
+                    <#ty as #try_into>::Error: std::convert::Into<
+                        std::boxed::Box<dyn std::error::Error + Send + Sync>
+                    >
+                });
+            }
+            predicates.push(quote_spanned!(ty.span()=> #into_ty: ::rusqlite::ToSql));
+        } else if self.serialize_with().is_none() {
+            predicates.push(quote_spanned!(ty.span()=> #ty: ::rusqlite::ToSql));
+        }
+
+        if self.has_type_info() {
+            predicates
+                .push(quote_spanned!(ty.span()=> #column_ty: rusqlite_mapper::SqliteTypeInfo));
+        }
+
+        Ok(())
+    }
+
+    /// Wether the column is described by the `SqliteTypeInfo` of `column_ty`. Not for
+    /// fields bound with `serialize_with` whose sql type is given by `with` or `sql_type`.
+    fn has_type_info(&self) -> bool {
+        self.serialize_with().is_none() || (self.with.is_none() && self.sql_type.is_none())
+    }
+
+    /// Wether this field is converted before binding it, then the params are
+    /// [`Param`](rusqlite_mapper::Param)s instead of `&dyn ToSql`.
+    fn converts(&self) -> bool {
+        self.serialize_with().is_some() || self.into.is_some() || self.try_into.is_some()
+    }
+
+    /// The Rust expression to access this field as a param, a `Param` if `converts` is set
+    fn param_ref(&self, converts: bool) -> Result<TokenStream2> {
         let Some(ident) = &self.ident else {
             return Ok(quote! {
-                ()
+                &() as &dyn ::rusqlite::ToSql
             });
         };
 
-        if self.value.is_some() {
-            return Ok(quote! {
-                self.#ident
+        let ty = &self.ty;
+        if !converts {
+            // Spanned to the field type so a missing `ToSql` impl is reported at the field.
+            return Ok(quote_spanned! {ty.span()=>
+                &self.#ident as &dyn ::rusqlite::ToSql
             });
         }
 
-        let outer_type_ident = if let syn::Type::Path(syn::TypePath {
-            path: syn::Path { segments, .. },
-            ..
-        }) = &self.ty
-        {
-            segments.last().map(|s| &s.ident)
-        } else {
-            None
-        };
+        if let Some(serialize) = self.serialize_with() {
+            return Ok(quote! {
+                rusqlite_mapper::Param::serialize_with(&self.#ident, #serialize)
+            });
+        }
 
-        let res = match outer_type_ident {
-            Some(id)
-                if id == "i8"
-                    || id == "i16"
-                    || id == "i32"
-                    || id == "i64"
-                    || id == "isize"
-                    || id == "u8"
-                    || id == "u16"
-                    || id == "u32"
-                    || id == "f32"
-                    || id == "f64"
-                    || id == "u64"
-                    || id == "usize"
-                    || id == "bool" =>
-            {
-                quote! { self.#ident }
-            }
-            _ => quote! { &self.#ident },
-        };
+        // `Into` implies `TryInto` with an infallible error, both convert when bound.
+        if let Some(into_ty) = self.param_ty()? {
+            return Ok(quote_spanned! {ty.span()=>
+                rusqlite_mapper::Param::convert::<#ty, #into_ty>(&self.#ident)
+            });
+        }
 
-        Ok(res)
+        // Spanned to the field type so a missing `ToSql` impl is reported at the field.
+        Ok(quote_spanned! {ty.span()=>
+            rusqlite_mapper::Param::Borrowed(&self.#ident)
+        })
     }
 }
 
-fn type_to_param_ty(ty: &syn::Type, force_as_value: bool) -> Result<TokenStream2> {
-    if force_as_value {
-        return Ok(quote! { #ty });
+/// Turns an optional string into an `Option<&'static str>` expression.
+fn quote_option(value: Option<&str>) -> TokenStream2 {
+    match value {
+        Some(value) => quote!(Some(#value)),
+        None => quote!(None),
     }
+}
 
-    let ty = match &ty {
-        syn::Type::Path(syn::TypePath {
-            path: syn::Path { segments, .. },
-            ..
-        }) => match segments {
-            segments
-                if segments.len() == 1
-                    && matches!(
-                        segments[0].ident.to_string().as_str(),
-                        "i8" | "i16"
-                            | "i32"
-                            | "i64"
-                            | "isize"
-                            | "u8"
-                            | "u16"
-                            | "u32"
-                            | "f32"
-                            | "f64"
-                            | "u64"
-                            | "usize"
-                            | "bool"
-                    ) =>
+impl References {
+    /// The `ForeignKey` expression of this reference. `self_ident` and `container` describe
+    /// the struct the field belongs to, which is needed for self references.
+    fn foreign_key(&self, self_ident: &syn::Ident, container: &SqliteContainer) -> TokenStream2 {
+        let table = match &self.target {
+            ReferenceTarget::Table(table) => quote!(#table),
+            // A type can't read its own `TABLE` while defining it.
+            ReferenceTarget::Type(syn::Type::Path(path))
+                if path.path.is_ident("Self") || path.path.is_ident(self_ident) =>
             {
-                let ident = &segments[0].ident;
-                quote! { #ident }
+                let table = container.table_name(self_ident);
+                quote!(#table)
             }
-
-            segments if segments.last().map_or(false, |s| s.ident == "Vec") => {
-                let segment = segments.last().unwrap();
-                let ty = match &segment.arguments {
-                    syn::PathArguments::AngleBracketed(args) => {
-                        let ty = args.args.first().unwrap();
-                        match ty {
-                            syn::GenericArgument::Type(ty) => ty,
-                            _ => panic!("invalid shape"),
-                        }
-                    }
-                    _ => panic!("invalid shape"),
-                };
-                quote! { &'a [#ty] }
+            ReferenceTarget::Type(ty) => {
+                quote_spanned!(ty.span()=> <#ty as rusqlite_mapper::ToRow>::TABLE.name)
             }
+        };
+        let column = quote_option(self.column.as_deref());
+        let on_delete = quote_action(self.on_delete);
+        let on_update = quote_action(self.on_update);
+        let deferrable = self.deferrable;
 
-            _ => quote! { &'a #ty },
-        },
+        quote! {
+            rusqlite_mapper::ForeignKey {
+                table: #table,
+                column: #column,
+                on_delete: #on_delete,
+                on_update: #on_update,
+                deferrable: #deferrable,
+            }
+        }
+    }
+}
 
-        _ => ty.to_token_stream(),
+/// Turns an optional foreign key action into an `Option<ForeignKeyAction>` expression.
+fn quote_action(action: Option<ForeignKeyAction>) -> TokenStream2 {
+    let action = match action {
+        Some(ForeignKeyAction::SetNull) => quote!(SetNull),
+        Some(ForeignKeyAction::SetDefault) => quote!(SetDefault),
+        Some(ForeignKeyAction::Cascade) => quote!(Cascade),
+        Some(ForeignKeyAction::Restrict) => quote!(Restrict),
+        Some(ForeignKeyAction::NoAction) => quote!(NoAction),
+        None => return quote!(None),
     };
-
-    Ok(ty)
+    quote!(Some(rusqlite_mapper::ForeignKeyAction::#action))
 }
@@ -1,4 +1,9 @@
-use darling::FromField;
+use darling::{Error, FromField, FromMeta};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+
+use crate::container::SqliteContainer;
 
 /// A single field inside of a struct that derives `FromRow`
 #[derive(Debug, FromField)]
@@ -8,6 +13,8 @@ pub(crate) struct SqliteField {
     pub(crate) ident: Option<syn::Ident>,
     /// The type specified in this field.
     pub(crate) ty: syn::Type,
+    /// The forwarded attributes of this field, used to read doc comments.
+    pub(crate) attrs: Vec<syn::Attribute>,
     /// Wether to flatten this field. Flattening means calling the `FromRow` implementation
     /// of `self.ty` instead of extracting it directly from the row.
     #[darling(default)]
@@ -21,33 +28,342 @@ pub(crate) struct SqliteField {
     /// Optionaly use this type as the target for `FromRow` or `FromSql`, and then
     /// call `From::from` to convert it the `self.ty`.
     pub(crate) from: Option<String>,
+    /// Optionaly call `TryInto::try_into` on a clone of the field to convert it to this type
+    /// before binding it as a param.
+    pub(crate) try_into: Option<String>,
+    /// Optionaly call `Into::into` on a clone of the field to convert it to this type before
+    /// binding it as a param.
+    pub(crate) into: Option<String>,
+    /// A module providing `from_sql`, `to_sql` and `sqlite_type` functions used instead of
+    /// `FromSql`, `ToSql` and `SqliteTypeInfo`, like serde's `with`.
+    pub(crate) with: Option<syn::Path>,
+    /// A function `fn(ValueRef) -> FromSqlResult<T>` used instead of `FromSql`.
+    pub(crate) deserialize_with: Option<syn::Path>,
+    /// A function `fn(&T) -> rusqlite::Result<ToSqlOutput>` used instead of `ToSql`.
+    pub(crate) serialize_with: Option<syn::Path>,
     /// Override the name of the actual sql column instead of using `self.ident`.
     /// Is not compatible with `flatten` since no column is needed there.
     pub(crate) rename: Option<String>,
+    /// Read the column at this position, relative to the first column of the struct,
+    /// instead of looking it up by name.
+    pub(crate) position: Option<usize>,
 
     /// Indicates that this field is the primary key of the table.
     pub(crate) primary_key: Option<()>,
 
+    /// The primary key is assigned by the database: it is left out of inserts and the
+    /// generated rowid is written back by `ToRow::insert`. Upserts and `INSERT OR REPLACE`
+    /// therefore never conflict on this key.
+    pub(crate) auto: Option<()>,
+
+    /// Leave this column out of inserts so it is filled by its sql `DEFAULT` or a trigger.
+    pub(crate) skip_insert: Option<()>,
+
+    /// Declares the primary key as `AUTOINCREMENT`.
+    pub(crate) autoincrement: Option<()>,
+
+    /// Override the sql type of the column instead of using `SqliteTypeInfo::sqlite_type`.
+    pub(crate) sql_type: Option<String>,
+
+    /// An sql expression used as the `DEFAULT` of the column.
+    pub(crate) default: Option<String>,
+
+    /// Use a default value when the column is sql `null`, see [`DefaultValue`].
+    pub(crate) default_value: Option<DefaultValue>,
+
+    /// Use the default value (`Default::default()` unless `default_value` is set) when the
+    /// column is not in the result set.
+    pub(crate) default_if_missing: Option<()>,
+
+    /// Adds a `UNIQUE` constraint to the column.
+    pub(crate) unique: Option<()>,
+
+    /// An sql expression used as the `CHECK` constraint of the column.
+    pub(crate) check: Option<String>,
+
+    /// The collation sequence of the column, e.g. `NOCASE`.
+    pub(crate) collate: Option<String>,
+
+    /// Creates an index on this column.
+    pub(crate) index: Option<()>,
+
+    /// Creates a unique index on this column.
+    pub(crate) unique_index: Option<()>,
+
+    /// Declares the column as a foreign key, see [`References`].
+    pub(crate) references: Option<References>,
+
     /// Ignore this field for any Sql related operations.
     pub(crate) skip: Option<()>,
 
-    /// Normally, non atomic types are handed to rusqlite as references when
-    /// used as params. But some types like Uuid should be handed as values.
-    /// This attribute allows to specify that.
-    pub(crate) value: Option<()>,
+    /// Used to hand non atomic types to rusqlite as values instead of references.
+    /// All params are now passed as `&dyn ToSql`, so this attribute is only kept
+    /// for backwards compatibility and has no effect.
+    #[darling(rename = "value")]
+    pub(crate) _value: Option<()>,
 }
 
 impl SqliteField {
     /// Returns the name that maps to the actuall sql column
-    /// By default this is the same as the rust field name but can be overwritten by `#[from_row(rename = "..")]`.
-    pub(crate) fn column_name(&self) -> String {
-        self.rename
-            .as_ref()
-            .map(Clone::clone)
-            .unwrap_or_else(|| self.ident.as_ref().unwrap().to_string())
+    /// By default this is the same as the rust field name but can be overwritten by `#[from_row(rename = "..")]`
+    /// or by the struct level `#[rusqlite(rename_all = "..")]`.
+    pub(crate) fn column_name(&self, container: &SqliteContainer) -> String {
+        self.rename.clone().unwrap_or_else(|| {
+            let name = self.ident.as_ref().unwrap().to_string();
+            match container.rename_all {
+                Some(rule) => rule.apply(&name),
+                None => name,
+            }
+        })
     }
 
     pub(crate) fn is_primary_key(&self) -> bool {
         self.primary_key.is_some()
     }
+
+    /// The expression producing the default value of this field, used by `default_value`
+    /// and `default_if_missing`.
+    pub(crate) fn default_expr(&self) -> TokenStream {
+        match &self.default_value {
+            Some(DefaultValue::Path(path)) => quote!(#path()),
+            _ => quote!(std::default::Default::default()),
+        }
+    }
+
+    /// The function reading this field, set by `deserialize_with` or `with`.
+    pub(crate) fn deserialize_with(&self) -> Option<TokenStream> {
+        match (&self.deserialize_with, &self.with) {
+            (Some(path), _) => Some(quote!(#path)),
+            (None, Some(with)) => Some(quote!(#with::from_sql)),
+            (None, None) => None,
+        }
+    }
+
+    /// The function binding this field, set by `serialize_with` or `with`.
+    pub(crate) fn serialize_with(&self) -> Option<TokenStream> {
+        match (&self.serialize_with, &self.with) {
+            (Some(path), _) => Some(quote!(#path)),
+            (None, Some(with)) => Some(quote!(#with::to_sql)),
+            (None, None) => None,
+        }
+    }
+
+    /// Parses the type `ty` of a conversion attribute, spanned to the field type so errors
+    /// about it are reported at the field.
+    fn parse_ty(&self, ty: &str) -> syn::Result<TokenStream> {
+        let ty = syn::LitStr::new(ty, self.ty.span()).parse::<syn::Type>()?;
+        Ok(ty.to_token_stream())
+    }
+
+    /// The type this field is converted to before binding it, set by `into` or `try_into`.
+    pub(crate) fn param_ty(&self) -> syn::Result<Option<TokenStream>> {
+        match self.into.as_ref().or(self.try_into.as_ref()) {
+            Some(ty) => Ok(Some(self.parse_ty(ty)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The type whose `SqliteTypeInfo` describes the column: the target of `into` or
+    /// `try_into`, else the source of `from` or `try_from`, else the field type.
+    pub(crate) fn column_ty(&self) -> syn::Result<TokenStream> {
+        let ty = self
+            .into
+            .as_ref()
+            .or(self.try_into.as_ref())
+            .or(self.from.as_ref())
+            .or(self.try_from.as_ref());
+        match ty {
+            Some(ty) => self.parse_ty(ty),
+            None => Ok(self.ty.to_token_stream()),
+        }
+    }
+
+    /// Checks that the conversions of this field don't conflict: `with` can't be combined
+    /// with `deserialize_with` or `serialize_with`, and only one way of binding the field
+    /// can be used.
+    pub(crate) fn validate_conversions(&self) -> darling::Result<()> {
+        if self.with.is_some() && (self.deserialize_with.is_some() || self.serialize_with.is_some())
+        {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(with = "..")]` with `#[rusqlite(deserialize_with = "..")]` or `#[rusqlite(serialize_with = "..")]`"#,
+            )
+            .with_span(&self.ty));
+        }
+
+        if self.into.is_some() && self.try_into.is_some() {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(into = "..")]` with `#[rusqlite(try_into = "..")]`"#,
+            )
+            .with_span(&self.ty));
+        }
+
+        if self.serialize_with().is_some() && (self.into.is_some() || self.try_into.is_some()) {
+            return Err(Error::custom(
+                r#"can't combine `#[rusqlite(serialize_with = "..")]` or `#[rusqlite(with = "..")]` with `#[rusqlite(into = "..")]` or `#[rusqlite(try_into = "..")]`"#,
+            )
+            .with_span(&self.ty));
+        }
+
+        Ok(())
+    }
+
+    /// Wether the type of this field is spelled `Option<..>`. Used for fields converted with
+    /// `serialize_with`, which don't need to implement `SqliteTypeInfo`.
+    pub(crate) fn is_option(&self) -> bool {
+        match &self.ty {
+            syn::Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Option"),
+            _ => false,
+        }
+    }
+}
+
+/// The field level `default_value` attribute.
+#[derive(Debug, Clone)]
+pub(crate) enum DefaultValue {
+    /// `#[rusqlite(default_value)]` uses `Default::default()`.
+    Default,
+    /// `#[rusqlite(default_value = "path::to::fn")]` calls the function.
+    Path(syn::Path),
+}
+
+impl FromMeta for DefaultValue {
+    fn from_word() -> darling::Result<Self> {
+        Ok(Self::Default)
+    }
+
+    fn from_string(value: &str) -> darling::Result<Self> {
+        syn::parse_str(value).map(Self::Path).map_err(|_| {
+            Error::custom(
+                r#"expected the path of a function, e.g. `default_value = "path::to::fn"`"#,
+            )
+        })
+    }
+}
+
+/// Joins the lines of all `///` doc comments in `attrs`, if there are any.
+pub(crate) fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) => Some(doc.value().trim().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// A foreign key declared with `#[rusqlite(references = "table(column)")]` or
+/// `#[rusqlite(references(ty = "Type", column = "..", on_delete = "..", on_update = "..", deferrable))]`.
+#[derive(Debug)]
+pub(crate) struct References {
+    pub(crate) target: ReferenceTarget,
+    pub(crate) column: Option<String>,
+    pub(crate) on_delete: Option<ForeignKeyAction>,
+    pub(crate) on_update: Option<ForeignKeyAction>,
+    pub(crate) deferrable: bool,
+}
+
+/// The table a foreign key points to.
+#[derive(Debug)]
+pub(crate) enum ReferenceTarget {
+    /// A table referenced by name.
+    Table(String),
+    /// A type implementing `ToRow`, its table name is used.
+    Type(syn::Type),
+}
+
+/// The list form of `#[rusqlite(references(..))]`.
+#[derive(Debug, FromMeta)]
+struct ReferencesList {
+    ty: Option<syn::Type>,
+    table: Option<String>,
+    column: Option<String>,
+    on_delete: Option<ForeignKeyAction>,
+    on_update: Option<ForeignKeyAction>,
+    #[darling(default)]
+    deferrable: bool,
+}
+
+impl FromMeta for References {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        let (table, column) = match value.split_once('(') {
+            Some((table, column)) => {
+                let column = column
+                    .strip_suffix(')')
+                    .ok_or_else(|| Error::custom(r#"expected `"table(column)"`"#))?;
+                (table.trim(), Some(column.trim().to_string()))
+            }
+            None => (value.trim(), None),
+        };
+
+        Ok(Self {
+            target: ReferenceTarget::Table(table.to_string()),
+            column,
+            on_delete: None,
+            on_update: None,
+            deferrable: false,
+        })
+    }
+
+    fn from_list(items: &[darling::ast::NestedMeta]) -> darling::Result<Self> {
+        let list = ReferencesList::from_list(items)?;
+
+        let target = match (list.ty, list.table) {
+            (Some(ty), None) => ReferenceTarget::Type(ty),
+            (None, Some(table)) => ReferenceTarget::Table(table),
+            _ => {
+                return Err(Error::custom(
+                    r#"`references(..)` needs exactly one of `ty = ".."` or `table = ".."`"#,
+                ))
+            }
+        };
+
+        Ok(Self {
+            target,
+            column: list.column,
+            on_delete: list.on_delete,
+            on_update: list.on_update,
+            deferrable: list.deferrable,
+        })
+    }
+}
+
+/// A foreign key action like `on_delete = "cascade"`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ForeignKeyAction {
+    SetNull,
+    SetDefault,
+    Cascade,
+    Restrict,
+    NoAction,
+}
+
+impl FromMeta for ForeignKeyAction {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        match value.to_lowercase().replace('_', " ").as_str() {
+            "set null" => Ok(Self::SetNull),
+            "set default" => Ok(Self::SetDefault),
+            "cascade" => Ok(Self::Cascade),
+            "restrict" => Ok(Self::Restrict),
+            "no action" => Ok(Self::NoAction),
+            _ => Err(Error::unknown_value(value)),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+mod container;
 mod derive_from_row;
 mod derive_sqlite_value;
 mod derive_to_row;
@@ -0,0 +1,166 @@
+use rusqlite::{Connection, Statement};
+
+use crate::{ColumnResolver, FromRow};
+
+/// Prepares `sql` and checks that it returns all columns needed by `T`, see
+/// [`check_columns`]. Extra columns are allowed, use [`prepare_checked_strict`] to
+/// reject them.
+///
+/// ```ignore
+/// let mut stmt = prepare_checked::<Todo>(&conn, "SELECT id, txt FROM todo")?;
+/// // Err: columns of `Todo` don't match the statement: missing `text` (did you mean `txt`?)
+/// ```
+pub fn prepare_checked<'conn, T: FromRow>(
+    conn: &'conn Connection,
+    sql: &str,
+) -> Result<Statement<'conn>, PrepareError> {
+    let stmt = conn.prepare(sql)?;
+    check_columns::<T>(&stmt, false)?;
+    Ok(stmt)
+}
+
+/// Like [`prepare_checked`] but also fails if the statement returns columns that are not
+/// read by `T`.
+pub fn prepare_checked_strict<'conn, T: FromRow>(
+    conn: &'conn Connection,
+    sql: &str,
+) -> Result<Statement<'conn>, PrepareError> {
+    let stmt = conn.prepare(sql)?;
+    check_columns::<T>(&stmt, true)?;
+    Ok(stmt)
+}
+
+/// Compares the columns of `stmt` against the columns needed by `T`, including the
+/// prefixed columns of flattened fields. Fails if a column is missing or, if `strict` is
+/// set, if the statement returns a column that is not read by `T`.
+pub fn check_columns<T: FromRow>(stmt: &Statement, strict: bool) -> Result<(), PrepareError> {
+    let (used, missing) =
+        ColumnResolver::check(stmt, |resolver| T::resolve_columns(resolver, None))?;
+
+    let expected = T::columns(None);
+    let missing = missing
+        .into_iter()
+        .map(|(slot, err)| match err {
+            rusqlite::Error::InvalidColumnName(name) => name,
+            // Columns read by position are named after their field, if the type lists it.
+            err => expected.get(slot).cloned().unwrap_or_else(|| match err {
+                rusqlite::Error::InvalidColumnIndex(index) => format!("#{index}"),
+                err => err.to_string(),
+            }),
+        })
+        .collect::<Vec<_>>();
+    let unused = stmt
+        .column_names()
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !used.contains(&Some(*index)))
+        .map(|(_, name)| name.to_string())
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() && (unused.is_empty() || !strict) {
+        return Ok(());
+    }
+
+    let suggestions = missing
+        .iter()
+        .filter_map(|name| {
+            closest_match(name, &unused).map(|candidate| (name.clone(), candidate.to_string()))
+        })
+        .collect();
+    let extra = if strict { unused } else { Vec::new() };
+
+    Err(PrepareError::Columns(ColumnMismatch {
+        type_name: std::any::type_name::<T>(),
+        missing,
+        extra,
+        suggestions,
+    }))
+}
+
+/// The column in `candidates` that is most similar to `name`, if any is close enough to
+/// be a likely typo.
+fn closest_match<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    candidates
+        .iter()
+        .map(|candidate| {
+            let distance = strsim::damerau_levenshtein(&name, &candidate.to_lowercase());
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// The error returned by [`prepare_checked`].
+#[derive(Debug)]
+pub enum PrepareError {
+    /// Preparing the statement failed.
+    Sqlite(rusqlite::Error),
+    /// The columns of the statement don't match the type.
+    Columns(ColumnMismatch),
+}
+
+impl From<rusqlite::Error> for PrepareError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Sqlite(err)
+    }
+}
+
+impl std::fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sqlite(err) => err.fmt(f),
+            Self::Columns(mismatch) => mismatch.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PrepareError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sqlite(err) => Some(err),
+            Self::Columns(_) => None,
+        }
+    }
+}
+
+/// The differences between the columns of a statement and the columns needed by a
+/// [`FromRow`] type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMismatch {
+    /// The name of the type the statement was checked against.
+    pub type_name: &'static str,
+    /// The columns needed by the type that the statement doesn't return.
+    pub missing: Vec<String>,
+    /// The columns returned by the statement that are not read by the type. Only listed
+    /// by [`prepare_checked_strict`] and strict checks.
+    pub extra: Vec<String>,
+    /// Pairs of a missing column and a similar extra column, likely a typo.
+    pub suggestions: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for ColumnMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "columns of `{}` don't match the statement",
+            self.type_name
+        )?;
+        let mut separator = ":";
+        for name in &self.missing {
+            write!(f, "{separator} missing `{name}`")?;
+            if let Some((_, candidate)) = self.suggestions.iter().find(|(m, _)| m == name) {
+                write!(f, " (did you mean `{candidate}`?)")?;
+            }
+            separator = ",";
+        }
+        for name in &self.extra {
+            write!(f, "{separator} unexpected `{name}`")?;
+            separator = ",";
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ColumnMismatch {}
@@ -0,0 +1,144 @@
+use rusqlite::types::Type;
+
+/// An error mapping a column to a field of a struct deriving [`FromRow`](crate::FromRow).
+///
+/// The derived `FromRow` implementations return it wrapped in
+/// [`rusqlite::Error::FromSqlConversionFailure`], so it works inside `query_map` closures.
+/// Errors that don't come from a column, like [`rusqlite::Error::InvalidColumnName`], are
+/// returned as they are. Use [`MapperError::downcast`] to get it back:
+///
+/// ```ignore
+/// let err = conn.query_row(sql, [], Todo::try_from_row).unwrap_err();
+/// let err = MapperError::downcast(&err).unwrap();
+/// assert_eq!(err.field_path(), "Todo.author.role.kind");
+/// ```
+#[derive(Debug)]
+pub struct MapperError {
+    /// The name of the outermost struct that was mapped.
+    pub struct_name: &'static str,
+    /// The fields from `struct_name` down to the field that failed, e.g.
+    /// `["author", "role", "kind"]`.
+    pub fields: Vec<&'static str>,
+    /// The name of the column including its prefix, e.g. `author_role_kind`.
+    pub column: Option<String>,
+    /// The index of the column in the statement.
+    pub column_index: Option<usize>,
+    /// The name of the rust type of the field.
+    pub expected_type: Option<&'static str>,
+    /// The storage class of the value in the column.
+    pub storage_class: Option<Type>,
+    /// The underlying error.
+    pub source: rusqlite::Error,
+}
+
+impl MapperError {
+    /// Returns the [`MapperError`] wrapped in `err`, if there is one.
+    pub fn downcast(err: &rusqlite::Error) -> Option<&MapperError> {
+        match err {
+            rusqlite::Error::FromSqlConversionFailure(_, _, err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// The struct name and the fields joined with `.`, e.g. `Todo.author.role.kind`.
+    pub fn field_path(&self) -> String {
+        std::iter::once(self.struct_name)
+            .chain(self.fields.iter().copied())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Wraps the error `source` of reading the column at `index` into the field `field` of
+    /// the struct `struct_name`. Used by the derived `FromRow` implementations.
+    #[doc(hidden)]
+    pub fn column(
+        source: rusqlite::Error,
+        row: &rusqlite::Row,
+        index: usize,
+        struct_name: &'static str,
+        field: &'static str,
+        expected_type: &'static str,
+    ) -> rusqlite::Error {
+        let stmt: &rusqlite::Statement = row.as_ref();
+        Self {
+            struct_name,
+            fields: vec![field],
+            column: stmt.column_name(index).ok().map(str::to_string),
+            column_index: Some(index),
+            expected_type: Some(expected_type),
+            storage_class: row.get_ref(index).ok().map(|value| value.data_type()),
+            source,
+        }
+        .into()
+    }
+
+    /// Adds the flattened field `field` of the struct `struct_name` to the path of the
+    /// error `source` of mapping that field. Used by the derived `FromRow` implementations.
+    ///
+    /// Errors that aren't about the value of a column, like a missing column, are returned
+    /// unchanged.
+    #[doc(hidden)]
+    pub fn nested(
+        source: rusqlite::Error,
+        struct_name: &'static str,
+        field: &'static str,
+    ) -> rusqlite::Error {
+        let mut err = match source {
+            rusqlite::Error::FromSqlConversionFailure(index, ty, err) => {
+                match err.downcast::<MapperError>() {
+                    Ok(err) => *err,
+                    // E.g. from a manual `FromRow` impl, only the index and type are known.
+                    Err(err) => Self {
+                        struct_name,
+                        fields: Vec::new(),
+                        column: None,
+                        column_index: Some(index),
+                        expected_type: None,
+                        storage_class: Some(ty.clone()),
+                        source: rusqlite::Error::FromSqlConversionFailure(index, ty, err),
+                    },
+                }
+            }
+            source => return source,
+        };
+        err.struct_name = struct_name;
+        err.fields.insert(0, field);
+        err.into()
+    }
+}
+
+impl std::fmt::Display for MapperError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to map `{}`", self.field_path())?;
+        if let Some(expected_type) = self.expected_type {
+            write!(f, " of type `{expected_type}`")?;
+        }
+        if let Some(column) = &self.column {
+            write!(f, " from column `{column}`")?;
+        }
+        if let Some(storage_class) = &self.storage_class {
+            write!(f, " holding {storage_class}")?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl std::error::Error for MapperError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Wraps `err` in [`rusqlite::Error::FromSqlConversionFailure`] with the index and storage
+/// class of its column. Without them there is nothing to wrap into, so the source error is
+/// returned.
+impl From<MapperError> for rusqlite::Error {
+    fn from(err: MapperError) -> Self {
+        match (err.column_index, err.storage_class.clone()) {
+            (Some(index), Some(storage_class)) => {
+                rusqlite::Error::FromSqlConversionFailure(index, storage_class, Box::new(err))
+            }
+            _ => err.source,
+        }
+    }
+}
@@ -1,5 +1,27 @@
+use std::marker::PhantomData;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+
+use crate::{quote_identifier, row_plan::column_matches, ColumnCursor, ColumnResolver, RowPlan};
+
 /// A trait that allows mapping a [`rusqlite::Row`] to other types.
+///
+/// The name based methods like [`FromRow::try_from_row`] look up the columns of a single
+/// row. For many rows of a statement the columns are resolved to indices once with
+/// [`FromRow::resolve_columns`] and then read by index, see [`RowPlan`].
+///
+/// Implementations provide [`FromRow::try_from_row_prefixed`] and [`FromRow::is_all_null`].
+/// Unless the indexed methods are implemented as well, a plan calls them for every row.
 pub trait FromRow: Sized {
+    /// The width of the range of columns read by this type, from the first to the last
+    /// column including flattened fields. A column read twice with `position = N` is only
+    /// counted once. `0` for types reading their columns by name.
+    const COLUMN_COUNT: usize = 0;
+
+    /// Wether this type, or a type flattened into it, reads columns by position. The name
+    /// based methods of such types have to resolve a [`RowPlan`] first.
+    const BY_POSITION: bool = false;
+
     /// Performs the conversion.
     ///
     /// # Panics
@@ -39,9 +61,73 @@ pub trait FromRow: Sized {
     ///
     /// Will return an error if the row does not contain the expected column names.
     fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error>;
+
+    /// The names of all columns read by this type, each prefixed with `prefix`, including
+    /// the columns of flattened fields with their prefixes. Types mapped
+    /// `#[rusqlite(by_index)]` list the names of their fields.
+    /// Empty for types that don't provide it.
+    fn columns(prefix: Option<&str>) -> Vec<String> {
+        let _ = prefix;
+        Vec::new()
+    }
+
+    /// Renders a select list for [`FromRow::columns`] that reads the columns from the
+    /// table `alias` and renames them with `prefix`, e.g.
+    /// `User::select_list("a", "author_")` gives `a."id" AS "author_id", a."name" AS "author_name"`.
+    /// The columns are not qualified if `alias` is empty.
+    fn select_list(alias: &str, prefix: &str) -> String {
+        Self::columns(None)
+            .iter()
+            .map(|column| {
+                let source = quote_identifier(column);
+                let target = quote_identifier(&format!("{prefix}{column}"));
+                if alias.is_empty() {
+                    format!("{source} AS {target}")
+                } else {
+                    format!("{alias}.{source} AS {target}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Resolves the indices of all columns needed by this type, in the order they are
+    /// read by [`FromRow::try_from_row_indexed`]. Each column is looked up by its name
+    /// prefixed with `prefix`, or by position for types using `#[rusqlite(by_index)]`.
+    ///
+    /// By default the columns are looked up by name for every row, see
+    /// [`ColumnResolver::resolve_by_name`].
+    fn resolve_columns(
+        resolver: &mut ColumnResolver<'_>,
+        prefix: Option<&str>,
+    ) -> Result<(), rusqlite::Error> {
+        resolver.resolve_by_name(prefix);
+        Ok(())
+    }
+
+    /// Performs the conversion, reading the columns at the indices resolved by
+    /// [`FromRow::resolve_columns`].
+    fn try_from_row_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<Self, rusqlite::Error> {
+        Self::try_from_row_prefixed(row, cursor.next_prefix())
+    }
+
+    /// Checks if all the columns at the indices resolved by [`FromRow::resolve_columns`]
+    /// are sql 'null' values. Advances `cursor` past all of them.
+    fn is_all_null_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<bool, rusqlite::Error> {
+        Self::is_all_null(row, cursor.next_prefix())
+    }
 }
 
 impl<T: FromRow> FromRow for Option<T> {
+    const COLUMN_COUNT: usize = T::COLUMN_COUNT;
+    const BY_POSITION: bool = T::BY_POSITION;
+
     fn try_from_row_prefixed(
         row: &rusqlite::Row,
         prefix: Option<&str>,
@@ -56,4 +142,394 @@ impl<T: FromRow> FromRow for Option<T> {
     fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error> {
         T::is_all_null(row, prefix)
     }
+
+    fn columns(prefix: Option<&str>) -> Vec<String> {
+        T::columns(prefix)
+    }
+
+    fn resolve_columns(
+        resolver: &mut ColumnResolver<'_>,
+        prefix: Option<&str>,
+    ) -> Result<(), rusqlite::Error> {
+        T::resolve_columns(resolver, prefix)
+    }
+
+    fn try_from_row_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<Self, rusqlite::Error> {
+        let mut columns = cursor.clone();
+        if T::is_all_null_indexed(row, &mut columns)? {
+            *cursor = columns;
+            Ok(None)
+        } else {
+            Ok(Some(T::try_from_row_indexed(row, cursor)?))
+        }
+    }
+
+    fn is_all_null_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<bool, rusqlite::Error> {
+        T::is_all_null_indexed(row, cursor)
+    }
+}
+
+/// A single column mapped with [`rusqlite::types::FromSql`], read by position. Use
+/// [`Column`] to read a column by name.
+///
+/// Useful to map single column queries or to mix plain values with structs in tuples:
+///
+/// ```ignore
+/// let ids: Vec<Scalar<i64>> = conn.query_as("SELECT id FROM user", [])?;
+/// let rows: Vec<(User, Scalar<i64>)> =
+///     conn.query_as("SELECT u.id, u.name, count(*) FROM user u JOIN todo t ..", [])?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Scalar<T>(pub T);
+
+impl<T> Scalar<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for Scalar<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: FromSql> FromRow for Scalar<T> {
+    const COLUMN_COUNT: usize = 1;
+    const BY_POSITION: bool = true;
+
+    fn try_from_row_prefixed(
+        row: &rusqlite::Row,
+        prefix: Option<&str>,
+    ) -> Result<Self, rusqlite::Error> {
+        RowPlan::<Self>::prefixed(row.as_ref(), prefix)?.try_from_row(row)
+    }
+
+    fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error> {
+        RowPlan::<Self>::prefixed(row.as_ref(), prefix)?.is_all_null(row)
+    }
+
+    /// Empty, the column has no name.
+    fn columns(_prefix: Option<&str>) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn resolve_columns(
+        resolver: &mut ColumnResolver<'_>,
+        _prefix: Option<&str>,
+    ) -> Result<(), rusqlite::Error> {
+        resolver.resolve_next()
+    }
+
+    fn try_from_row_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<Self, rusqlite::Error> {
+        row.get(cursor.next_index()).map(Self)
+    }
+
+    fn is_all_null_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<bool, rusqlite::Error> {
+        Ok(row.get_ref(cursor.next_index())? == ValueRef::Null)
+    }
+}
+
+/// The name of the column read by [`Column`].
+///
+/// ```ignore
+/// struct Total;
+///
+/// impl ColumnName for Total {
+///     const NAME: &'static str = "total";
+/// }
+/// ```
+pub trait ColumnName {
+    const NAME: &'static str;
+}
+
+/// A single column mapped with [`rusqlite::types::FromSql`], read by its name
+/// `N::NAME` with the prefix, like a field of a struct.
+///
+/// ```ignore
+/// let rows: Vec<(User, Column<i64, Total>)> =
+///     conn.query_as("SELECT u.id, u.name, count(*) AS total FROM user u JOIN todo t ..", [])?;
+/// ```
+pub struct Column<T, N>(pub T, PhantomData<N>);
+
+impl<T, N> Column<T, N> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, N> std::ops::Deref for Column<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: std::fmt::Debug, N> std::fmt::Debug for Column<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Column").field(&self.0).finish()
+    }
+}
+
+impl<T: FromSql, N: ColumnName> FromRow for Column<T, N> {
+    const COLUMN_COUNT: usize = 1;
+
+    fn try_from_row_prefixed(
+        row: &rusqlite::Row,
+        prefix: Option<&str>,
+    ) -> Result<Self, rusqlite::Error> {
+        let index = column_index(row, prefix, N::NAME)?;
+        Ok(Self(row.get(index)?, PhantomData))
+    }
+
+    fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error> {
+        Ok(row.get_ref(column_index(row, prefix, N::NAME)?)? == ValueRef::Null)
+    }
+
+    fn columns(prefix: Option<&str>) -> Vec<String> {
+        vec![prefix.unwrap_or("").to_string() + N::NAME]
+    }
+
+    fn resolve_columns(
+        resolver: &mut ColumnResolver<'_>,
+        prefix: Option<&str>,
+    ) -> Result<(), rusqlite::Error> {
+        resolver.resolve(prefix, N::NAME)
+    }
+
+    fn try_from_row_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<Self, rusqlite::Error> {
+        Ok(Self(row.get(cursor.next_index())?, PhantomData))
+    }
+
+    fn is_all_null_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<bool, rusqlite::Error> {
+        Ok(row.get_ref(cursor.next_index())? == ValueRef::Null)
+    }
+}
+
+/// The prefix used by [`Prefixed`].
+///
+/// ```ignore
+/// struct Author;
+///
+/// impl Prefix for Author {
+///     const PREFIX: &'static str = "author_";
+/// }
+/// ```
+pub trait Prefix {
+    const PREFIX: &'static str;
+}
+
+/// Maps `T` from the columns prefixed with `P::PREFIX`, like a field with
+/// `#[rusqlite(flatten, prefix = "..")]`. Useful for tuple elements.
+pub struct Prefixed<T, P>(pub T, PhantomData<P>);
+
+impl<T, P> Prefixed<T, P> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> std::ops::Deref for Prefixed<T, P> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: std::fmt::Debug, P> std::fmt::Debug for Prefixed<T, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Prefixed").field(&self.0).finish()
+    }
+}
+
+impl<T: FromRow, P: Prefix> FromRow for Prefixed<T, P> {
+    const COLUMN_COUNT: usize = T::COLUMN_COUNT;
+    const BY_POSITION: bool = T::BY_POSITION;
+
+    fn try_from_row_prefixed(
+        row: &rusqlite::Row,
+        prefix: Option<&str>,
+    ) -> Result<Self, rusqlite::Error> {
+        let prefix = prefix.unwrap_or("").to_string() + P::PREFIX;
+        Ok(Self(
+            T::try_from_row_prefixed(row, Some(&prefix))?,
+            PhantomData,
+        ))
+    }
+
+    fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error> {
+        T::is_all_null(row, Some(&(prefix.unwrap_or("").to_string() + P::PREFIX)))
+    }
+
+    fn columns(prefix: Option<&str>) -> Vec<String> {
+        T::columns(Some(&(prefix.unwrap_or("").to_string() + P::PREFIX)))
+    }
+
+    fn resolve_columns(
+        resolver: &mut ColumnResolver<'_>,
+        prefix: Option<&str>,
+    ) -> Result<(), rusqlite::Error> {
+        T::resolve_columns(
+            resolver,
+            Some(&(prefix.unwrap_or("").to_string() + P::PREFIX)),
+        )
+    }
+
+    fn try_from_row_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<Self, rusqlite::Error> {
+        Ok(Self(T::try_from_row_indexed(row, cursor)?, PhantomData))
+    }
+
+    fn is_all_null_indexed(
+        row: &rusqlite::Row,
+        cursor: &mut ColumnCursor<'_>,
+    ) -> Result<bool, rusqlite::Error> {
+        T::is_all_null_indexed(row, cursor)
+    }
+}
+
+/// Reads the column at `index` with a `deserialize_with` function, turning its error into
+/// the same [`rusqlite::Error`] that [`rusqlite::Row::get`] would return.
+#[doc(hidden)]
+pub fn read_with<T>(
+    row: &rusqlite::Row,
+    index: usize,
+    deserialize: impl FnOnce(ValueRef<'_>) -> FromSqlResult<T>,
+) -> rusqlite::Result<T> {
+    let value = row.get_ref(index)?;
+    deserialize(value).map_err(|err| match err {
+        FromSqlError::InvalidType => {
+            let stmt: &rusqlite::Statement = row.as_ref();
+            let name = stmt.column_name(index).unwrap_or_default().to_string();
+            rusqlite::Error::InvalidColumnType(index, name, value.data_type())
+        }
+        FromSqlError::OutOfRange(value) => rusqlite::Error::IntegralValueOutOfRange(index, value),
+        FromSqlError::Other(err) => {
+            rusqlite::Error::FromSqlConversionFailure(index, value.data_type(), err)
+        }
+        err => rusqlite::Error::FromSqlConversionFailure(index, value.data_type(), Box::new(err)),
+    })
 }
+
+/// The index of the column named `prefix` + `name` in `row`, like [`rusqlite::Row::get`]
+/// without joining the name.
+#[doc(hidden)]
+pub fn column_index(
+    row: &rusqlite::Row,
+    prefix: Option<&str>,
+    name: &str,
+) -> rusqlite::Result<usize> {
+    find_column(row, prefix, name)
+        .ok_or_else(|| rusqlite::Error::InvalidColumnName(prefix.unwrap_or("").to_string() + name))
+}
+
+/// Like [`column_index`], `None` if there is no such column.
+#[doc(hidden)]
+pub fn find_column(row: &rusqlite::Row, prefix: Option<&str>, name: &str) -> Option<usize> {
+    let stmt: &rusqlite::Statement = row.as_ref();
+    (0..stmt.column_count()).find(|&index| {
+        stmt.column_name(index)
+            .is_ok_and(|column| column_matches(column, prefix, name))
+    })
+}
+
+/// Tuples read their elements from consecutive column ranges, each `COLUMN_COUNT`
+/// columns wide. Columns are looked up by name only within the range of their element,
+/// so `SELECT u.*, r.* ..` can be mapped to `(User, Role)` even if both have an `id`.
+macro_rules! tuple_impl {
+    ($($name:ident)+) => {
+        impl<$($name: FromRow),+> FromRow for ($($name,)+) {
+            const COLUMN_COUNT: usize = 0 $(+ $name::COLUMN_COUNT)+;
+            const BY_POSITION: bool = true;
+
+            fn try_from_row_prefixed(
+                row: &rusqlite::Row,
+                prefix: Option<&str>,
+            ) -> Result<Self, rusqlite::Error> {
+                RowPlan::<Self>::prefixed(row.as_ref(), prefix)?.try_from_row(row)
+            }
+
+            fn is_all_null(
+                row: &rusqlite::Row,
+                prefix: Option<&str>,
+            ) -> Result<bool, rusqlite::Error> {
+                RowPlan::<Self>::prefixed(row.as_ref(), prefix)?.is_all_null(row)
+            }
+
+            fn columns(prefix: Option<&str>) -> Vec<String> {
+                let mut columns = Vec::with_capacity(Self::COLUMN_COUNT);
+                $(columns.extend($name::columns(prefix));)+
+                columns
+            }
+
+            fn resolve_columns(
+                resolver: &mut ColumnResolver<'_>,
+                prefix: Option<&str>,
+            ) -> Result<(), rusqlite::Error> {
+                let mut start = resolver.position();
+                $(
+                    let end = start + $name::COLUMN_COUNT;
+                    resolver.within(start..end, |resolver| $name::resolve_columns(resolver, prefix))?;
+                    start = end;
+                )+
+                resolver.seek(start);
+                Ok(())
+            }
+
+            fn try_from_row_indexed(
+                row: &rusqlite::Row,
+                cursor: &mut ColumnCursor<'_>,
+            ) -> Result<Self, rusqlite::Error> {
+                Ok(($($name::try_from_row_indexed(row, cursor)?,)+))
+            }
+
+            fn is_all_null_indexed(
+                row: &rusqlite::Row,
+                cursor: &mut ColumnCursor<'_>,
+            ) -> Result<bool, rusqlite::Error> {
+                // Not short circuiting, the cursor has to advance past all columns.
+                Ok(true $(& $name::is_all_null_indexed(row, cursor)?)+)
+            }
+        }
+    };
+}
+
+tuple_impl!(A);
+tuple_impl!(A B);
+tuple_impl!(A B C);
+tuple_impl!(A B C D);
+tuple_impl!(A B C D E);
+tuple_impl!(A B C D E F);
+tuple_impl!(A B C D E F G);
+tuple_impl!(A B C D E F G H);
+tuple_impl!(A B C D E F G H I);
+tuple_impl!(A B C D E F G H I J);
+tuple_impl!(A B C D E F G H I J K);
+tuple_impl!(A B C D E F G H I J K L);
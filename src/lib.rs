@@ -1,9 +1,27 @@
 // #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
 
+mod check;
+mod error;
 mod from_row;
+mod mapper_ext;
+mod row_plan;
+mod schema;
 mod to_row;
+mod upsert;
 
-pub use from_row::FromRow;
+pub use check::{
+    check_columns, prepare_checked, prepare_checked_strict, ColumnMismatch, PrepareError,
+};
+pub use error::MapperError;
+pub use from_row::{
+    column_index, find_column, read_with, Column, ColumnName, FromRow, Prefix, Prefixed, Scalar,
+};
+pub use mapper_ext::{FromRowIter, MapperExt, StatementExt};
+pub use row_plan::{ColumnCursor, ColumnResolver, RowPlan};
 pub use rusqlite_mapper_derive::{FromRow, SqliteValue, ToRow};
-pub use to_row::{SqliteTypeInfo, ToRow};
+pub use schema::{
+    dependency_order, ColumnDef, ForeignKey, ForeignKeyAction, IndexColumn, IndexDef, TableDef,
+};
+pub use to_row::{quote_identifier, FromRowId, Param, SqliteTypeInfo, ToRow};
+pub use upsert::{Upsert, UpsertError};
@@ -0,0 +1,222 @@
+use rusqlite::{Connection, OptionalExtension, Params, Rows, Savepoint, Statement, Transaction};
+
+use crate::{FromRow, RowPlan, ToRow};
+
+/// Typed CRUD operations on a [`Connection`], [`Transaction`] or [`Savepoint`], built on
+/// [`ToRow`] and [`FromRow`]. All statements are prepared with
+/// [`Connection::prepare_cached`].
+///
+/// ```ignore
+/// use rusqlite_mapper::MapperExt;
+///
+/// conn.insert(&mut person)?;
+/// let loaded = conn.get::<Person, _>([person.id])?;
+/// let everyone = conn.find_all::<Person>()?;
+/// ```
+pub trait MapperExt {
+    /// The connection the statements are run on.
+    fn connection(&self) -> &Connection;
+
+    /// Inserts `row` and assigns the generated rowid to its `#[rusqlite(auto)]` primary
+    /// key, see [`ToRow::insert`].
+    fn insert<T: ToRow>(&self, row: &mut T) -> rusqlite::Result<()> {
+        row.insert(self.connection())
+    }
+
+    /// Inserts all `rows` with a single prepared statement and returns the number of
+    /// inserted rows. The rows are not inserted atomically, use a [`Transaction`] for that.
+    fn insert_many<'r, T, I>(&self, rows: I) -> rusqlite::Result<usize>
+    where
+        T: ToRow + 'r,
+        I: IntoIterator<Item = &'r mut T>,
+    {
+        let conn = self.connection();
+        let mut stmt = conn.prepare_cached(&T::insert_stmt())?;
+        let mut count = 0;
+        for row in rows {
+            stmt.execute(row.to_params())?;
+            row.set_rowid(conn.last_insert_rowid())?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Inserts `row` or updates the existing row on conflict with the primary key, see
+    /// [`ToRow::upsert`]. Tables with an `#[rusqlite(auto)]` key conflict on their first
+    /// unique constraint, without one this fails with an [`UpsertError`](crate::UpsertError).
+    /// Returns the number of changed rows.
+    fn upsert<T: ToRow>(&self, row: &T) -> rusqlite::Result<usize> {
+        self.connection()
+            .prepare_cached(&T::upsert().to_sql()?)?
+            .execute(row.to_params())
+    }
+
+    /// Updates the row matching the primary key of `row`. Returns the number of changed
+    /// rows, i.e. `0` if there is no such row.
+    fn update<T: ToRow>(&self, row: &T) -> rusqlite::Result<usize> {
+        self.connection()
+            .prepare_cached(&T::update_stmt())?
+            .execute(row.to_update_params())
+    }
+
+    /// Deletes the row matching the primary key of `row`. Returns the number of deleted
+    /// rows, i.e. `0` if there is no such row.
+    fn delete<T: ToRow>(&self, row: &T) -> rusqlite::Result<usize> {
+        self.connection()
+            .prepare_cached(&T::delete_stmt())?
+            .execute(row.pk_params())
+    }
+
+    /// Loads the row with the primary key `pk`, given in the order of the key columns.
+    fn get<T: ToRow + FromRow, P: Params>(&self, pk: P) -> rusqlite::Result<Option<T>> {
+        self.query_opt_as(&T::select_by_pk_stmt(), pk)
+    }
+
+    /// Loads all rows of the table.
+    fn find_all<T: ToRow + FromRow>(&self) -> rusqlite::Result<Vec<T>> {
+        self.query_as(&T::select_all_stmt(), [])
+    }
+
+    /// Counts all rows of the table.
+    fn count<T: ToRow>(&self) -> rusqlite::Result<usize> {
+        self.connection()
+            .prepare_cached(&T::count_stmt())?
+            .query_row([], |row| row.get(0))
+    }
+
+    /// Wether a row with the primary key `pk` exists.
+    fn exists<T: ToRow, P: Params>(&self, pk: P) -> rusqlite::Result<bool> {
+        self.connection()
+            .prepare_cached(&T::exists_stmt())?
+            .query_row(pk, |row| row.get(0))
+    }
+
+    /// Runs the query `sql` and maps all rows to `T`.
+    fn query_as<T: FromRow, P: Params>(&self, sql: &str, params: P) -> rusqlite::Result<Vec<T>> {
+        self.connection().prepare_cached(sql)?.query_as(params)
+    }
+
+    /// Runs the query `sql` and maps the first row to `T`.
+    ///
+    /// Returns [`rusqlite::Error::QueryReturnedNoRows`] if there is no row.
+    fn query_one_as<T: FromRow, P: Params>(&self, sql: &str, params: P) -> rusqlite::Result<T> {
+        self.connection().prepare_cached(sql)?.query_one_as(params)
+    }
+
+    /// Runs the query `sql` and maps the first row to `T`, if there is one.
+    fn query_opt_as<T: FromRow, P: Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> rusqlite::Result<Option<T>> {
+        self.connection().prepare_cached(sql)?.query_opt_as(params)
+    }
+
+    /// Runs the query `sql` and passes an iterator that maps the rows to `T` one by one to
+    /// `f`. Unlike [`MapperExt::query_as`] the rows are not collected into a `Vec` first.
+    ///
+    /// ```ignore
+    /// let total = conn.query_iter_as("SELECT * FROM todo", [], |todos| {
+    ///     todos.map(|todo| todo.map(|todo: Todo| todo.text.len())).sum()
+    /// })?;
+    /// ```
+    fn query_iter_as<T, P, F, R>(&self, sql: &str, params: P, f: F) -> rusqlite::Result<R>
+    where
+        T: FromRow,
+        P: Params,
+        F: FnOnce(FromRowIter<'_, T>) -> rusqlite::Result<R>,
+    {
+        f(self
+            .connection()
+            .prepare_cached(sql)?
+            .query_iter_as(params)?)
+    }
+}
+
+impl MapperExt for Connection {
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+impl MapperExt for Transaction<'_> {
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+impl MapperExt for Savepoint<'_> {
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+/// The iterator returned by [`StatementExt::query_iter_as`]. The columns are resolved
+/// once when the first row is read, see [`RowPlan`].
+pub struct FromRowIter<'stmt, T> {
+    rows: Rows<'stmt>,
+    plan: Option<RowPlan<T>>,
+}
+
+impl<T: FromRow> Iterator for FromRowIter<'_, T> {
+    type Item = rusqlite::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match self.rows.next() {
+            Ok(row) => row?,
+            Err(err) => return Some(Err(err)),
+        };
+        let plan = match &self.plan {
+            Some(plan) => plan,
+            None => match RowPlan::new(row.as_ref()) {
+                Ok(plan) => self.plan.insert(plan),
+                Err(err) => return Some(Err(err)),
+            },
+        };
+        Some(plan.try_from_row(row))
+    }
+}
+
+/// Runs a prepared [`Statement`] and maps the rows to [`FromRow`] types.
+pub trait StatementExt {
+    /// Runs the query and maps all rows to `T`.
+    fn query_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<Vec<T>>;
+
+    /// Runs the query and maps the first row to `T`.
+    ///
+    /// Returns [`rusqlite::Error::QueryReturnedNoRows`] if there is no row.
+    fn query_one_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<T>;
+
+    /// Runs the query and maps the first row to `T`, if there is one.
+    fn query_opt_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<Option<T>>;
+
+    /// Runs the query and returns an iterator that maps the rows to `T` one by one.
+    fn query_iter_as<T: FromRow, P: Params>(
+        &mut self,
+        params: P,
+    ) -> rusqlite::Result<FromRowIter<'_, T>>;
+}
+
+impl StatementExt for Statement<'_> {
+    fn query_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<Vec<T>> {
+        self.query_iter_as(params)?.collect()
+    }
+
+    fn query_one_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<T> {
+        self.query_row(params, T::try_from_row)
+    }
+
+    fn query_opt_as<T: FromRow, P: Params>(&mut self, params: P) -> rusqlite::Result<Option<T>> {
+        self.query_one_as(params).optional()
+    }
+
+    fn query_iter_as<T: FromRow, P: Params>(
+        &mut self,
+        params: P,
+    ) -> rusqlite::Result<FromRowIter<'_, T>> {
+        Ok(FromRowIter {
+            rows: self.query(params)?,
+            plan: None,
+        })
+    }
+}
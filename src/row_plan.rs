@@ -0,0 +1,291 @@
+use std::{marker::PhantomData, ops::Range};
+
+use rusqlite::{Row, Statement};
+
+use crate::FromRow;
+
+/// The column indices a [`FromRow`] type reads, resolved once per statement.
+///
+/// Mapping a row through a plan reads every column by index, so there are no name
+/// lookups or allocations per row. The typed query helpers like
+/// [`StatementExt::query_as`](crate::StatementExt::query_as) use a plan internally.
+///
+/// ```ignore
+/// let mut stmt = conn.prepare("SELECT id, name FROM user")?;
+/// let plan = RowPlan::<User>::new(&stmt)?;
+/// let users = stmt
+///     .query_map([], |row| plan.try_from_row(row))?
+///     .collect::<Result<Vec<_>, _>>()?;
+/// ```
+pub struct RowPlan<T> {
+    indices: Vec<Option<usize>>,
+    prefixes: Vec<Option<String>>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: FromRow> RowPlan<T> {
+    /// Resolves the columns of `T` against the columns of `stmt`.
+    pub fn new(stmt: &Statement) -> rusqlite::Result<Self> {
+        Self::prefixed(stmt, None)
+    }
+
+    /// Resolves the columns of `T`, each prefixed with `prefix`, against the columns of
+    /// `stmt`.
+    pub fn prefixed(stmt: &Statement, prefix: Option<&str>) -> rusqlite::Result<Self> {
+        let mut resolver = ColumnResolver::new(stmt);
+        T::resolve_columns(&mut resolver, prefix)?;
+        Ok(Self {
+            indices: resolver.indices,
+            prefixes: resolver.prefixes,
+            _type: PhantomData,
+        })
+    }
+
+    /// Maps `row` to `T`. The row must come from the statement this plan was created for.
+    pub fn try_from_row(&self, row: &Row) -> rusqlite::Result<T> {
+        T::try_from_row_indexed(row, &mut self.cursor())
+    }
+
+    /// Checks if all columns read by `T` are sql `null` values in `row`.
+    pub fn is_all_null(&self, row: &Row) -> rusqlite::Result<bool> {
+        T::is_all_null_indexed(row, &mut self.cursor())
+    }
+
+    /// The resolved column indices in the order they are read. Columns of fields with
+    /// `#[rusqlite(default_if_missing)]` that are not in the statement are `None`.
+    pub fn indices(&self) -> &[Option<usize>] {
+        &self.indices
+    }
+
+    fn cursor(&self) -> ColumnCursor<'_> {
+        ColumnCursor {
+            indices: self.indices.iter(),
+            prefixes: self.prefixes.iter(),
+        }
+    }
+}
+
+impl<T> Clone for RowPlan<T> {
+    fn clone(&self) -> Self {
+        Self {
+            indices: self.indices.clone(),
+            prefixes: self.prefixes.clone(),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RowPlan<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RowPlan")
+            .field("indices", &self.indices)
+            .field("prefixes", &self.prefixes)
+            .finish()
+    }
+}
+
+/// The numbers of the resolve calls that failed with their errors.
+type MissingColumns = Vec<(usize, rusqlite::Error)>;
+
+/// Collects the column indices of a [`RowPlan`], see [`FromRow::resolve_columns`].
+///
+/// The resolver keeps track of a position, the index after the last resolved column.
+/// Types mapped with `#[rusqlite(by_index)]` read their columns starting from there.
+pub struct ColumnResolver<'stmt> {
+    columns: Vec<&'stmt str>,
+    indices: Vec<Option<usize>>,
+    /// The prefixes of types read by name, see [`ColumnResolver::resolve_by_name`].
+    prefixes: Vec<Option<String>>,
+    position: usize,
+    /// The columns searched by [`ColumnResolver::resolve`].
+    range: Range<usize>,
+    /// If set, columns that can't be resolved are recorded here, by the number of the
+    /// resolve call and the error, instead of failing.
+    missing: Option<MissingColumns>,
+}
+
+impl<'stmt> ColumnResolver<'stmt> {
+    /// A resolver for the columns of `stmt`.
+    pub fn new(stmt: &'stmt Statement) -> Self {
+        let columns = stmt.column_names();
+        Self {
+            range: 0..columns.len(),
+            columns,
+            indices: Vec::new(),
+            prefixes: Vec::new(),
+            position: 0,
+            missing: None,
+        }
+    }
+
+    /// A resolver that records missing columns instead of failing, used to check
+    /// statements. Returns the resolved indices and the numbers of the resolve calls that
+    /// failed with their errors.
+    pub(crate) fn check(
+        stmt: &'stmt Statement,
+        resolve: impl FnOnce(&mut Self) -> rusqlite::Result<()>,
+    ) -> rusqlite::Result<(Vec<Option<usize>>, MissingColumns)> {
+        let mut resolver = Self::new(stmt);
+        resolver.missing = Some(Vec::new());
+        resolve(&mut resolver)?;
+        Ok((resolver.indices, resolver.missing.unwrap_or_default()))
+    }
+
+    /// The index after the furthest resolved column, `0` at the start. Resolving a column
+    /// before it doesn't move it back, only [`ColumnResolver::seek`] does.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the position to `position`, so the next column read by position is the one at
+    /// this index.
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Resolves the column named `prefix` + `name`. Like [`rusqlite::Row::get`] the
+    /// first column matching case insensitively is used.
+    ///
+    /// Returns [`rusqlite::Error::InvalidColumnName`] if there is no such column.
+    pub fn resolve(&mut self, prefix: Option<&str>, name: &str) -> rusqlite::Result<()> {
+        match self.find(prefix, name) {
+            Some(index) => self.push(index),
+            None => {
+                let prefix = prefix.unwrap_or("");
+                self.fail(rusqlite::Error::InvalidColumnName(format!(
+                    "{prefix}{name}"
+                )))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`ColumnResolver::resolve`] but a missing column is not an error, it is read
+    /// as `None` by [`ColumnCursor::next_optional_index`].
+    pub fn resolve_optional(&mut self, prefix: Option<&str>, name: &str) {
+        match self.find(prefix, name) {
+            Some(index) => self.push(index),
+            None => self.indices.push(None),
+        }
+    }
+
+    /// Defers the lookup to a type that reads its columns by name when the row is mapped.
+    /// `prefix` is passed to its [`FromRow::try_from_row_prefixed`], see
+    /// [`ColumnCursor::next_prefix`]. Used by the provided [`FromRow::resolve_columns`].
+    pub fn resolve_by_name(&mut self, prefix: Option<&str>) {
+        self.prefixes.push(prefix.map(str::to_string));
+    }
+
+    /// The index of the first column named `prefix` + `name` in the current range.
+    fn find(&self, prefix: Option<&str>, name: &str) -> Option<usize> {
+        self.range
+            .clone()
+            .find(|&index| column_matches(self.columns[index], prefix, name))
+    }
+
+    /// Resolves the column at `index`.
+    ///
+    /// Returns [`rusqlite::Error::InvalidColumnIndex`] if the statement has no such column.
+    pub fn resolve_index(&mut self, index: usize) -> rusqlite::Result<()> {
+        if index >= self.columns.len() {
+            return self.fail(rusqlite::Error::InvalidColumnIndex(index));
+        }
+        self.push(index);
+        Ok(())
+    }
+
+    /// Resolves the column at the current position, see [`ColumnResolver::position`].
+    pub fn resolve_next(&mut self) -> rusqlite::Result<()> {
+        self.resolve_index(self.position)
+    }
+
+    /// Runs `resolve` with the position at `range.start` and name lookups limited to the
+    /// columns in `range`. Used to map tuples, where every element reads its own range.
+    pub(crate) fn within<R>(
+        &mut self,
+        range: Range<usize>,
+        resolve: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let end = range.end.min(self.columns.len());
+        let outer = std::mem::replace(&mut self.range, range.start.min(end)..end);
+        self.position = range.start;
+        let result = resolve(self);
+        self.range = outer;
+        result
+    }
+
+    /// Fails with `err` or records the missing column when checking.
+    fn fail(&mut self, err: rusqlite::Error) -> rusqlite::Result<()> {
+        match &mut self.missing {
+            Some(missing) => {
+                missing.push((self.indices.len() + missing.len(), err));
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    fn push(&mut self, index: usize) {
+        self.indices.push(Some(index));
+        // Going back with `seek` to read a column again doesn't move the following
+        // columns.
+        self.position = self.position.max(index + 1);
+    }
+}
+
+/// Yields the column indices of a [`RowPlan`] in the order they were resolved, see
+/// [`FromRow::try_from_row_indexed`].
+#[derive(Debug, Clone)]
+pub struct ColumnCursor<'plan> {
+    indices: std::slice::Iter<'plan, Option<usize>>,
+    prefixes: std::slice::Iter<'plan, Option<String>>,
+}
+
+impl<'plan> ColumnCursor<'plan> {
+    /// The index of the next column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more columns are read than were resolved, i.e. if
+    /// [`FromRow::resolve_columns`] and [`FromRow::try_from_row_indexed`] disagree.
+    pub fn next_index(&mut self) -> usize {
+        self.next_optional_index()
+            .expect("read a missing column that was resolved as optional")
+    }
+
+    /// The index of the next column, or `None` if it was resolved with
+    /// [`ColumnResolver::resolve_optional`] and is not in the statement.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more columns are read than were resolved.
+    pub fn next_optional_index(&mut self) -> Option<usize> {
+        *self
+            .indices
+            .next()
+            .expect("read more columns than were resolved")
+    }
+
+    /// The prefix of the next type read by name, recorded with
+    /// [`ColumnResolver::resolve_by_name`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if more types are read by name than were resolved.
+    pub fn next_prefix(&mut self) -> Option<&'plan str> {
+        self.prefixes
+            .next()
+            .expect("read more columns by name than were resolved")
+            .as_deref()
+    }
+}
+
+/// Wether `column` is named `prefix` + `name`, compared case insensitively like
+/// [`rusqlite::Row::get`].
+pub(crate) fn column_matches(column: &str, prefix: Option<&str>, name: &str) -> bool {
+    let prefix = prefix.unwrap_or("");
+    let column = column.as_bytes();
+    column.len() == prefix.len() + name.len()
+        && column[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+        && column[prefix.len()..].eq_ignore_ascii_case(name.as_bytes())
+}
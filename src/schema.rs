@@ -0,0 +1,396 @@
+use crate::{quote_identifier, to_row::quote_identifiers};
+
+/// Describes a table as derived by [`crate::ToRow`]. This is the single source of truth
+/// the generated statements are built from and can also be used for tooling like schema
+/// dumps, migrations or documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableDef {
+    /// The name of the table.
+    pub name: &'static str,
+    /// The schema the table lives in, e.g. the name of an attached database.
+    pub schema: Option<&'static str>,
+    /// The columns of the table in the order of the struct fields.
+    pub columns: &'static [ColumnDef],
+    /// Wether the table is a `WITHOUT ROWID` table.
+    pub without_rowid: bool,
+    /// Wether the table is a `STRICT` table.
+    pub strict: bool,
+    /// Table level `UNIQUE` constraints, each a list of column names.
+    pub unique: &'static [&'static [&'static str]],
+    /// Table level `CHECK` constraints.
+    pub checks: &'static [&'static str],
+    /// The indexes of the table.
+    pub indexes: &'static [IndexDef],
+    /// The doc comment of the struct.
+    pub doc: Option<&'static str>,
+}
+
+/// Describes a single column of a [`TableDef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnDef {
+    /// The name of the column.
+    pub name: &'static str,
+    /// The declared sql type, e.g. `INTEGER` or `TEXT`.
+    pub sql_type: &'static str,
+    /// Wether the column may contain `NULL` values.
+    pub nullable: bool,
+    /// Wether the column is (part of) the primary key. If multiple columns are marked,
+    /// the table has a composite primary key.
+    pub primary_key: bool,
+    /// Wether the primary key is declared as `AUTOINCREMENT`.
+    pub autoincrement: bool,
+    /// The sql expression of the `DEFAULT` constraint.
+    pub default: Option<&'static str>,
+    /// Wether the column has a `UNIQUE` constraint.
+    pub unique: bool,
+    /// The collation sequence, e.g. `NOCASE`.
+    pub collate: Option<&'static str>,
+    /// The foreign key this column references.
+    pub references: Option<ForeignKey>,
+    /// The sql expression of the `CHECK` constraint.
+    pub check: Option<&'static str>,
+    /// The doc comment of the field.
+    pub doc: Option<&'static str>,
+}
+
+/// Describes an index of a [`TableDef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexDef {
+    /// The name of the index.
+    pub name: &'static str,
+    /// The indexed columns or expressions.
+    pub columns: &'static [IndexColumn],
+    /// Wether this is a `UNIQUE` index.
+    pub unique: bool,
+    /// The `WHERE` clause of a partial index.
+    pub where_clause: Option<&'static str>,
+}
+
+/// A single entry of an [`IndexDef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexColumn {
+    /// A column, referenced by name.
+    Column(&'static str),
+    /// An sql expression, e.g. `lower(email)` or `created_at DESC`.
+    Expr(&'static str),
+}
+
+/// A foreign key reference of a [`ColumnDef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// The referenced table.
+    pub table: &'static str,
+    /// The referenced column. If `None` the primary key of `table` is referenced.
+    pub column: Option<&'static str>,
+    /// The `ON DELETE` action.
+    pub on_delete: Option<ForeignKeyAction>,
+    /// The `ON UPDATE` action.
+    pub on_update: Option<ForeignKeyAction>,
+    /// Wether the constraint is `DEFERRABLE INITIALLY DEFERRED`.
+    pub deferrable: bool,
+}
+
+/// The action taken by a [`ForeignKey`] when the referenced row is deleted or updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignKeyAction {
+    SetNull,
+    SetDefault,
+    Cascade,
+    Restrict,
+    NoAction,
+}
+
+impl TableDef {
+    /// The quoted table name prefixed with its schema, if any.
+    pub fn qualified_name(&self) -> String {
+        match self.schema {
+            Some(schema) => format!(
+                "{}.{}",
+                quote_identifier(schema),
+                quote_identifier(self.name)
+            ),
+            None => quote_identifier(self.name),
+        }
+    }
+
+    /// Looks up a column by name.
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The columns that make up the primary key.
+    pub fn primary_key(&self) -> impl Iterator<Item = &ColumnDef> {
+        self.columns.iter().filter(|column| column.primary_key)
+    }
+
+    /// The names of the columns that make up the primary key.
+    pub fn primary_key_names(&self) -> Vec<&'static str> {
+        self.primary_key().map(|column| column.name).collect()
+    }
+
+    /// Wether the table has a primary key. Usable in const contexts.
+    pub const fn has_primary_key(&self) -> bool {
+        let mut i = 0;
+        while i < self.columns.len() {
+            if self.columns[i].primary_key {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// Wether the table has columns that are not part of the primary key. Usable in const
+    /// contexts.
+    pub const fn has_non_key_columns(&self) -> bool {
+        let mut i = 0;
+        while i < self.columns.len() {
+            if !self.columns[i].primary_key {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// All sets of columns that uniquely identify a row: the primary key followed by
+    /// column level `UNIQUE` constraints, table level `UNIQUE` constraints and unique,
+    /// non partial indexes over plain columns.
+    pub fn unique_keys(&self) -> Vec<Vec<&'static str>> {
+        let mut keys = Vec::new();
+        let primary_key = self.primary_key_names();
+        if !primary_key.is_empty() {
+            keys.push(primary_key);
+        }
+        keys.extend(
+            self.columns
+                .iter()
+                .filter(|column| column.unique)
+                .map(|column| vec![column.name]),
+        );
+        keys.extend(self.unique.iter().map(|columns| columns.to_vec()));
+        keys.extend(
+            self.indexes
+                .iter()
+                .filter(|index| index.unique && index.where_clause.is_none())
+                .filter_map(|index| {
+                    index
+                        .columns
+                        .iter()
+                        .map(|column| match column {
+                            IndexColumn::Column(name) => Some(*name),
+                            IndexColumn::Expr(_) => None,
+                        })
+                        .collect::<Option<Vec<_>>>()
+                }),
+        );
+        keys
+    }
+
+    /// The names of the other tables this table references through foreign keys.
+    pub fn dependencies(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.columns
+            .iter()
+            .filter_map(|column| column.references.map(|references| references.table))
+            .filter(move |table| *table != self.name)
+    }
+
+    /// Renders the `CREATE INDEX IF NOT EXISTS` statements for all indexes of this table.
+    pub fn create_indexes_statements(&self) -> Vec<String> {
+        self.indexes
+            .iter()
+            .map(|index| index.create_index_statement(self))
+            .collect()
+    }
+
+    /// Renders the statements to create this table and all of its indexes.
+    pub fn create_schema_statements(&self) -> Vec<String> {
+        let mut statements = vec![self.create_table_statement()];
+        statements.extend(self.create_indexes_statements());
+        statements
+    }
+
+    /// Renders the `DROP TABLE` statement for this table.
+    pub fn drop_table_statement(&self) -> String {
+        format!("DROP TABLE {}", self.qualified_name())
+    }
+
+    /// Renders the `CREATE TABLE` statement for this table. A single primary key column
+    /// is declared inline, a composite key as a table level `PRIMARY KEY (..)` constraint.
+    pub fn create_table_statement(&self) -> String {
+        let primary_key = self.primary_key_names();
+        let composite_key = primary_key.len() > 1;
+
+        let mut definitions = self
+            .columns
+            .iter()
+            .map(|column| column.definition(!composite_key))
+            .collect::<Vec<_>>();
+
+        if composite_key {
+            definitions.push(format!("PRIMARY KEY ({})", quote_identifiers(&primary_key)));
+        }
+        for unique in self.unique {
+            definitions.push(format!("UNIQUE ({})", quote_identifiers(unique)));
+        }
+        for check in self.checks {
+            definitions.push(format!("CHECK ({check})"));
+        }
+
+        let mut stmt = String::from("CREATE TABLE ");
+        stmt.push_str(&self.qualified_name());
+        stmt.push_str(" (");
+        stmt.push_str(&definitions.join(", "));
+        stmt.push(')');
+
+        let mut options = Vec::new();
+        if self.without_rowid {
+            options.push("WITHOUT ROWID");
+        }
+        if self.strict {
+            options.push("STRICT");
+        }
+        if !options.is_empty() {
+            stmt.push(' ');
+            stmt.push_str(&options.join(", "));
+        }
+
+        stmt
+    }
+}
+
+impl ColumnDef {
+    /// Renders the column definition as used in a `CREATE TABLE` statement,
+    /// e.g. `"name" TEXT NOT NULL`. The `PRIMARY KEY` constraint is only rendered
+    /// if `inline_primary_key` is set.
+    pub(crate) fn definition(&self, inline_primary_key: bool) -> String {
+        let mut def = quote_identifier(self.name);
+        def.push(' ');
+        def.push_str(self.sql_type);
+        if self.primary_key && inline_primary_key {
+            def.push_str(" PRIMARY KEY");
+            if self.autoincrement {
+                def.push_str(" AUTOINCREMENT");
+            }
+        } else if !self.nullable {
+            def.push_str(" NOT NULL");
+        }
+        if self.unique {
+            def.push_str(" UNIQUE");
+        }
+        if let Some(check) = self.check {
+            def.push_str(" CHECK (");
+            def.push_str(check);
+            def.push(')');
+        }
+        if let Some(default) = self.default {
+            def.push_str(" DEFAULT ");
+            def.push_str(default);
+        }
+        if let Some(collate) = self.collate {
+            def.push_str(" COLLATE ");
+            def.push_str(collate);
+        }
+        if let Some(references) = &self.references {
+            def.push(' ');
+            def.push_str(&references.clause());
+        }
+        def
+    }
+}
+
+impl IndexDef {
+    /// Renders the `CREATE INDEX IF NOT EXISTS` statement of this index on `table`.
+    pub fn create_index_statement(&self, table: &TableDef) -> String {
+        let mut stmt = String::from("CREATE ");
+        if self.unique {
+            stmt.push_str("UNIQUE ");
+        }
+        stmt.push_str("INDEX IF NOT EXISTS ");
+        if let Some(schema) = table.schema {
+            stmt.push_str(&quote_identifier(schema));
+            stmt.push('.');
+        }
+        stmt.push_str(&quote_identifier(self.name));
+        stmt.push_str(" ON ");
+        stmt.push_str(&quote_identifier(table.name));
+        stmt.push_str(" (");
+        stmt.push_str(
+            &self
+                .columns
+                .iter()
+                .map(|column| match column {
+                    IndexColumn::Column(name) => quote_identifier(name),
+                    IndexColumn::Expr(expr) => expr.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        stmt.push(')');
+        if let Some(where_clause) = self.where_clause {
+            stmt.push_str(" WHERE ");
+            stmt.push_str(where_clause);
+        }
+        stmt
+    }
+}
+
+impl ForeignKey {
+    /// Renders the `REFERENCES` clause of this foreign key.
+    pub fn clause(&self) -> String {
+        let mut clause = String::from("REFERENCES ");
+        clause.push_str(&quote_identifier(self.table));
+        if let Some(column) = self.column {
+            clause.push('(');
+            clause.push_str(&quote_identifier(column));
+            clause.push(')');
+        }
+        if let Some(action) = self.on_delete {
+            clause.push_str(" ON DELETE ");
+            clause.push_str(action.as_sql());
+        }
+        if let Some(action) = self.on_update {
+            clause.push_str(" ON UPDATE ");
+            clause.push_str(action.as_sql());
+        }
+        if self.deferrable {
+            clause.push_str(" DEFERRABLE INITIALLY DEFERRED");
+        }
+        clause
+    }
+}
+
+impl ForeignKeyAction {
+    /// The sql keywords of this action, e.g. `SET NULL`.
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ForeignKeyAction::SetNull => "SET NULL",
+            ForeignKeyAction::SetDefault => "SET DEFAULT",
+            ForeignKeyAction::Cascade => "CASCADE",
+            ForeignKeyAction::Restrict => "RESTRICT",
+            ForeignKeyAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+/// Sorts `tables` so that every table comes after the tables it references. This is the
+/// order to create tables in, reverse it to drop them. Tables that are part of a reference
+/// cycle keep their relative order and are appended at the end.
+pub fn dependency_order(tables: &[&'static TableDef]) -> Vec<&'static TableDef> {
+    let mut remaining = tables.to_vec();
+    let mut sorted = Vec::with_capacity(tables.len());
+
+    loop {
+        let (ready, blocked): (Vec<_>, Vec<_>) = remaining.iter().partition(|table| {
+            table
+                .dependencies()
+                .all(|dependency| !remaining.iter().any(|other| other.name == dependency))
+        });
+        if ready.is_empty() {
+            sorted.extend(blocked);
+            return sorted;
+        }
+        sorted.extend(ready);
+        remaining = blocked;
+    }
+}
@@ -1,87 +1,529 @@
+use std::marker::PhantomData;
+
+use rusqlite::{types::ToSqlOutput, ToSql};
+
+use crate::{FromRow, TableDef, Upsert};
+
 /// A trait that maps a struct to a row in a database.
 pub trait ToRow: Sized {
+    /// The params returned by [`ToRow::to_params`]. Every field is borrowed as
+    /// `&dyn ToSql`, so any field type implementing [`rusqlite::ToSql`] can be used.
+    /// If a field is converted with `serialize_with`, `into` or `try_into` the fields are
+    /// bound as [`Param`]s instead.
+    /// Keys marked with `#[rusqlite(auto)]` are left out.
     type Params<'a>: rusqlite::Params
     where
         Self: 'a;
 
-    fn table_name() -> &'static str;
+    /// The params returned by [`ToRow::to_update_params`]: all non key columns
+    /// followed by the primary key columns.
+    type UpdateParams<'a>: rusqlite::Params
+    where
+        Self: 'a;
+
+    /// The params returned by [`ToRow::pk_params`]: the primary key columns.
+    type PkParams<'a>: rusqlite::Params
+    where
+        Self: 'a;
+
+    /// The static description of the table this struct maps to.
+    const TABLE: &'static TableDef;
+
+    fn table_name() -> &'static str {
+        Self::TABLE.name
+    }
+
+    /// The schema the table lives in, e.g. the name of an attached database.
+    /// Set with `#[rusqlite(schema = "..")]`.
+    fn schema_name() -> Option<&'static str> {
+        Self::TABLE.schema
+    }
+
+    /// The quoted table name prefixed with its schema, if any. This is the name used in
+    /// all generated statements.
+    fn qualified_table_name() -> String {
+        Self::TABLE.qualified_name()
+    }
 
     fn column_names() -> &'static [&'static str];
 
-    /// Returns a list of (sql data type name, optional, primary key)
-    fn sql_types() -> Vec<(String, bool, bool)>;
+    /// The columns used by [`ToRow::insert_stmt`]: all columns but the primary key if
+    /// it is marked with `#[rusqlite(auto)]` and columns marked with `#[rusqlite(skip_insert)]`.
+    fn insert_column_names() -> &'static [&'static str] {
+        Self::column_names()
+    }
+
+    /// Assigns the rowid generated by an insert to the `#[rusqlite(auto)]` primary key.
+    /// Does nothing if there is no such key.
+    fn set_rowid(&mut self, _rowid: i64) -> rusqlite::Result<()> {
+        Ok(())
+    }
 
     fn to_params(&self) -> Self::Params<'_>;
 
+    /// The params for [`ToRow::update_stmt`].
+    fn to_update_params(&self) -> Self::UpdateParams<'_>;
+
+    /// The params for [`ToRow::delete_stmt`] and [`ToRow::select_by_pk_stmt`].
+    fn pk_params(&self) -> Self::PkParams<'_>;
+
     fn create_table_statement() -> String {
-        let mut stmt = String::from("CREATE TABLE ");
-        stmt.push_str(Self::table_name());
-        stmt.push_str(" (");
-        stmt.push_str(
-            &Self::column_names()
-                .iter()
-                .zip(Self::sql_types().iter())
-                .map(|(name, (ty, optional, primary_key))| {
-                    let mut stmt = String::from(*name);
-                    stmt.push(' ');
-                    stmt.push_str(ty);
-                    if *primary_key {
-                        stmt.push_str(" PRIMARY KEY");
-                    } else if !*optional {
-                        stmt.push_str(" NOT NULL");
-                    }
-                    stmt
-                })
-                .collect::<Vec<_>>()
-                .join(", "),
-        );
-        stmt.push(')');
-        stmt
+        Self::TABLE.create_table_statement()
+    }
+
+    /// The `CREATE INDEX IF NOT EXISTS` statements for all declared indexes.
+    fn create_indexes_statements() -> Vec<String> {
+        Self::TABLE.create_indexes_statements()
+    }
+
+    /// The `CREATE TABLE` statement followed by [`ToRow::create_indexes_statements`].
+    fn create_schema_statements() -> Vec<String> {
+        Self::TABLE.create_schema_statements()
+    }
+
+    fn drop_table_statement() -> String {
+        Self::TABLE.drop_table_statement()
     }
 
     fn insert_stmt() -> String {
-        let mut stmt = String::from("INSERT INTO ");
-        stmt.push_str(Self::table_name());
-        stmt.push_str(" (");
-        stmt.push_str(&Self::column_names().to_vec().join(", "));
-        stmt.push_str(") VALUES (");
-        stmt.push_str(
-            &Self::column_names()
-                .iter()
-                .map(|_| "?")
-                .collect::<Vec<_>>()
-                .join(", "),
-        );
-        stmt.push(')');
+        insert_stmt::<Self>("INSERT")
+    }
+
+    /// Inserts this row and assigns the generated rowid to the `#[rusqlite(auto)]`
+    /// primary key, if there is one.
+    fn insert(&mut self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        conn.prepare_cached(&Self::insert_stmt())?
+            .execute(self.to_params())?;
+        self.set_rowid(conn.last_insert_rowid())
+    }
+
+    /// Like [`ToRow::insert_stmt`] but returns the inserted row, including the generated
+    /// key and columns filled by sql `DEFAULT`s. The returned columns are the ones read by
+    /// the `FromRow` implementation.
+    fn insert_returning_stmt() -> String
+    where
+        Self: FromRow,
+    {
+        let mut stmt = Self::insert_stmt();
+        stmt.push_str(&returning_clause::<Self>());
         stmt
     }
 
+    /// Inserts this row and returns it as stored in the database. Unlike
+    /// [`ToRow::insert`] this also picks up columns filled by sql `DEFAULT`s.
+    fn insert_returning(&self, conn: &rusqlite::Connection) -> rusqlite::Result<Self>
+    where
+        Self: FromRow,
+    {
+        conn.prepare_cached(&Self::insert_returning_stmt())?
+            .query_row(self.to_params(), Self::try_from_row)
+    }
+
+    /// Like [`ToRow::insert_stmt`] but replaces the existing row on conflict
+    /// (`INSERT OR REPLACE`).
+    ///
+    /// An `#[rusqlite(auto)]` key is left out of the insert, so only conflicts on unique
+    /// constraints replace a row, never conflicts on the primary key.
+    fn insert_or_replace_stmt() -> String {
+        insert_stmt::<Self>("INSERT OR REPLACE")
+    }
+
+    /// Like [`ToRow::insert_stmt`] but skips the insert on conflict (`INSERT OR IGNORE`).
+    fn insert_or_ignore_stmt() -> String {
+        insert_stmt::<Self>("INSERT OR IGNORE")
+    }
+
+    /// An insert statement that updates the existing row if it conflicts on `id`, a comma
+    /// separated list of columns that is inserted as is. All columns but `id` and the
+    /// primary key are updated, if there are none the insert is skipped (`DO NOTHING`).
+    #[deprecated(
+        note = "use `ToRow::upsert`, e.g. `T::upsert().to_sql()?` for the primary key or `T::upsert().on_conflict(..)`"
+    )]
     fn upsert_stmt(id: &str) -> String {
+        let target = id
+            .split(',')
+            .map(|name| name.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+        let columns = Self::TABLE
+            .columns
+            .iter()
+            .filter(|column| {
+                !column.primary_key
+                    && !target.contains(&column.name)
+                    && Self::insert_column_names().contains(&column.name)
+            })
+            .map(|column| {
+                let name = quote_identifier(column.name);
+                format!("{name} = excluded.{name}")
+            })
+            .collect::<Vec<_>>();
+
         let mut stmt = Self::insert_stmt();
         stmt.push_str(" ON CONFLICT (");
         stmt.push_str(id);
-        stmt.push_str(") DO UPDATE SET ");
+        if columns.is_empty() {
+            stmt.push_str(") DO NOTHING");
+        } else {
+            stmt.push_str(") DO UPDATE SET ");
+            stmt.push_str(&columns.join(", "));
+        }
+        stmt
+    }
+
+    /// A builder for upsert statements, see [`Upsert`]. `T::upsert().to_sql()?` updates
+    /// the existing row if it conflicts on the primary key (or the first declared unique
+    /// constraint if there is no primary key).
+    ///
+    /// An `#[rusqlite(auto)]` key is left out of the insert, so the inserted row never
+    /// conflicts on it. These tables conflict on the first unique constraint instead, and
+    /// [`Upsert::to_sql`] returns [`UpsertError::NotInserted`](crate::UpsertError::NotInserted)
+    /// if there is none.
+    fn upsert() -> Upsert<Self> {
+        Upsert::default()
+    }
+
+    /// Updates all non key columns of the row matching the primary key. Use with
+    /// [`ToRow::to_update_params`].
+    ///
+    /// Fails to compile if the table has no primary key or if all columns are part of it,
+    /// e.g. for an association table.
+    fn update_stmt() -> String {
+        const {
+            assert!(
+                Self::TABLE.has_primary_key(),
+                "`update_stmt` needs a `#[rusqlite(primary_key)]` field"
+            );
+            assert!(
+                Self::TABLE.has_non_key_columns(),
+                "`update_stmt` needs a column that is not part of the primary key"
+            );
+        }
+        let mut stmt = String::from("UPDATE ");
+        stmt.push_str(&Self::qualified_table_name());
+        stmt.push_str(" SET ");
         stmt.push_str(
-            &Self::column_names()
+            &Self::TABLE
+                .columns
                 .iter()
-                .map(|name| {
-                    let mut stmt = String::from(*name);
-                    stmt.push_str(" = excluded.");
-                    stmt.push_str(name);
-                    stmt
-                })
+                .filter(|column| !column.primary_key)
+                .map(|column| format!("{} = ?", quote_identifier(column.name)))
                 .collect::<Vec<_>>()
                 .join(", "),
         );
+        stmt.push_str(" WHERE ");
+        stmt.push_str(&primary_key_condition(Self::TABLE));
+        stmt
+    }
+
+    /// Like [`ToRow::update_stmt`] but returns the updated row, see
+    /// [`ToRow::insert_returning_stmt`].
+    ///
+    /// Fails to compile if the table has no primary key or if all columns are part of it.
+    fn update_returning_stmt() -> String
+    where
+        Self: FromRow,
+    {
+        let mut stmt = Self::update_stmt();
+        stmt.push_str(&returning_clause::<Self>());
+        stmt
+    }
+
+    /// Updates the row matching the primary key of this row and returns it as stored in
+    /// the database.
+    ///
+    /// Returns [`rusqlite::Error::QueryReturnedNoRows`] if there is no such row.
+    fn update_returning(&self, conn: &rusqlite::Connection) -> rusqlite::Result<Self>
+    where
+        Self: FromRow,
+    {
+        conn.prepare_cached(&Self::update_returning_stmt())?
+            .query_row(self.to_update_params(), Self::try_from_row)
+    }
+
+    /// Deletes the row matching the primary key. Use with [`ToRow::pk_params`].
+    ///
+    /// Fails to compile if the table has no primary key.
+    fn delete_stmt() -> String {
+        const {
+            assert!(
+                Self::TABLE.has_primary_key(),
+                "`delete_stmt` needs a `#[rusqlite(primary_key)]` field"
+            );
+        }
+        let mut stmt = String::from("DELETE FROM ");
+        stmt.push_str(&Self::qualified_table_name());
+        stmt.push_str(" WHERE ");
+        stmt.push_str(&primary_key_condition(Self::TABLE));
+        stmt
+    }
+
+    /// Like [`ToRow::delete_stmt`] but returns the deleted row, see
+    /// [`ToRow::insert_returning_stmt`].
+    ///
+    /// Fails to compile if the table has no primary key.
+    fn delete_returning_stmt() -> String
+    where
+        Self: FromRow,
+    {
+        let mut stmt = Self::delete_stmt();
+        stmt.push_str(&returning_clause::<Self>());
+        stmt
+    }
+
+    /// Selects all columns of the row matching the primary key. Use with [`ToRow::pk_params`].
+    ///
+    /// Fails to compile if the table has no primary key.
+    fn select_by_pk_stmt() -> String {
+        const {
+            assert!(
+                Self::TABLE.has_primary_key(),
+                "`select_by_pk_stmt` needs a `#[rusqlite(primary_key)]` field"
+            );
+        }
+        let mut stmt = Self::select_all_stmt();
+        stmt.push_str(" WHERE ");
+        stmt.push_str(&primary_key_condition(Self::TABLE));
         stmt
     }
+
+    /// Selects all columns of all rows.
+    fn select_all_stmt() -> String {
+        let mut stmt = String::from("SELECT ");
+        stmt.push_str(&quote_identifiers(Self::column_names()));
+        stmt.push_str(" FROM ");
+        stmt.push_str(&Self::qualified_table_name());
+        stmt
+    }
+
+    /// Counts all rows of the table.
+    fn count_stmt() -> String {
+        format!("SELECT COUNT(*) FROM {}", Self::qualified_table_name())
+    }
+
+    /// Selects wether a row matching the primary key exists. Use with [`ToRow::pk_params`].
+    ///
+    /// Fails to compile if the table has no primary key.
+    fn exists_stmt() -> String {
+        const {
+            assert!(
+                Self::TABLE.has_primary_key(),
+                "`exists_stmt` needs a `#[rusqlite(primary_key)]` field"
+            );
+        }
+        format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE {})",
+            Self::qualified_table_name(),
+            primary_key_condition(Self::TABLE)
+        )
+    }
+}
+
+/// Renders the insert statement of `T` starting with `verb`, e.g. `INSERT OR REPLACE`.
+/// Tables without inserted columns, e.g. only an `auto` key, insert `DEFAULT VALUES`.
+fn insert_stmt<T: ToRow>(verb: &str) -> String {
+    let mut stmt = String::from(verb);
+    stmt.push_str(" INTO ");
+    stmt.push_str(&T::qualified_table_name());
+    if T::insert_column_names().is_empty() {
+        stmt.push_str(" DEFAULT VALUES");
+        return stmt;
+    }
+    stmt.push_str(" (");
+    stmt.push_str(&quote_identifiers(T::insert_column_names()));
+    stmt.push_str(") VALUES (");
+    stmt.push_str(
+        &T::insert_column_names()
+            .iter()
+            .map(|_| "?")
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    stmt.push(')');
+    stmt
+}
+
+/// Renders the ` RETURNING ..` clause listing the columns read by the `FromRow`
+/// implementation of `T`.
+fn returning_clause<T: FromRow>() -> String {
+    let columns = T::columns(None);
+    let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+    format!(" RETURNING {}", quote_identifiers(&columns))
+}
+
+/// Renders the `WHERE` condition matching the primary key of `table`, e.g. `"id" = ?`.
+/// The callers check that there is a primary key at compile time.
+fn primary_key_condition(table: &TableDef) -> String {
+    table
+        .primary_key_names()
+        .iter()
+        .map(|name| format!("{} = ?", quote_identifier(name)))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Quotes an sql identifier like a table or column name with double quotes. Embedded
+/// double quotes are escaped by doubling them.
+pub fn quote_identifier(name: &str) -> String {
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    quoted.push_str(&name.replace('"', "\"\""));
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes and joins a list of identifiers with `, `.
+pub(crate) fn quote_identifiers(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A single param of the derived [`ToRow`] params of structs with converted fields:
+/// either a field borrowed as `&dyn ToSql` or a field converted with
+/// `#[rusqlite(serialize_with = "..")]`, `into = ".."` or `try_into = ".."`.
+pub enum Param<'a> {
+    /// A field implementing `ToSql`.
+    Borrowed(&'a dyn ToSql),
+    /// A field converted when it is bound.
+    Converted(Box<dyn ToSql + 'a>),
 }
 
+impl<'a> Param<'a> {
+    /// A param converting `value` with `serialize` when it is bound.
+    #[doc(hidden)]
+    pub fn serialize_with<T: ?Sized>(
+        value: &'a T,
+        serialize: fn(&T) -> rusqlite::Result<ToSqlOutput<'_>>,
+    ) -> Self {
+        Self::Converted(Box::new(SerializeWith { value, serialize }))
+    }
+
+    /// A param converting a clone of `value` to `U` with `TryInto`, or `Into` which implies
+    /// it, when it is bound.
+    #[doc(hidden)]
+    pub fn convert<T, U>(value: &'a T) -> Self
+    where
+        T: Clone + TryInto<U>,
+        T::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        U: ToSql + 'a,
+    {
+        Self::Converted(Box::new(Convert::<T, U> {
+            value,
+            _target: PhantomData,
+        }))
+    }
+}
+
+impl ToSql for Param<'_> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            Param::Borrowed(value) => value.to_sql(),
+            Param::Converted(value) => value.to_sql(),
+        }
+    }
+}
+
+/// Binds a value through a `serialize_with` function.
+struct SerializeWith<'a, T: ?Sized> {
+    value: &'a T,
+    serialize: fn(&T) -> rusqlite::Result<ToSqlOutput<'_>>,
+}
+
+impl<T: ?Sized> ToSql for SerializeWith<'_, T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        (self.serialize)(self.value)
+    }
+}
+
+/// Binds a value through an `into` or `try_into` conversion.
+struct Convert<'a, T, U> {
+    value: &'a T,
+    _target: PhantomData<fn() -> U>,
+}
+
+impl<T, U> ToSql for Convert<'_, T, U>
+where
+    T: Clone + TryInto<U>,
+    T::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    U: ToSql,
+{
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value: U = self
+            .value
+            .clone()
+            .try_into()
+            .map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))?;
+        // The converted value is dropped at the end of this call, so its output has to be owned.
+        match value.to_sql()? {
+            ToSqlOutput::Borrowed(value) => Ok(ToSqlOutput::Owned(value.into())),
+            ToSqlOutput::Owned(value) => Ok(ToSqlOutput::Owned(value)),
+            _ => Err(rusqlite::Error::ToSqlConversionFailure(
+                "`into` and `try_into` only support values, not zeroblobs or arrays".into(),
+            )),
+        }
+    }
+}
+
+/// Types that can hold a rowid generated by the database, used for `#[rusqlite(auto)]` keys.
+///
+/// A rowid that doesn't fit the type is returned as
+/// [`rusqlite::Error::ToSqlConversionFailure`].
+pub trait FromRowId: Sized {
+    fn from_row_id(rowid: i64) -> rusqlite::Result<Self>;
+}
+
+impl<T: FromRowId> FromRowId for Option<T> {
+    fn from_row_id(rowid: i64) -> rusqlite::Result<Self> {
+        T::from_row_id(rowid).map(Some)
+    }
+}
+
+macro_rules! impl_from_row_id {
+    ($($ty:ty),*) => {$(
+        impl FromRowId for $ty {
+            fn from_row_id(rowid: i64) -> rusqlite::Result<Self> {
+                // The rowid is not read from a column, so there is no column index to report.
+                <$ty>::try_from(rowid).map_err(|_| {
+                    rusqlite::Error::ToSqlConversionFailure(
+                        format!("rowid {rowid} is out of range for `{}`", stringify!($ty)).into(),
+                    )
+                })
+            }
+        }
+    )*};
+}
+
+impl_from_row_id!(i32, i64, isize, u32, u64, usize);
+
+/// Describes the sql type of a column holding a rust type, used by the derived
+/// [`ToRow::TABLE`].
+///
+/// The type is given as constants so it can be used in the `&'static` table
+/// description. Implementations that overrode `fn sqlite_type()` and `fn optional()`
+/// move their values to [`SqliteTypeInfo::SQLITE_TYPE`] and [`SqliteTypeInfo::OPTIONAL`]:
+///
+/// ```ignore
+/// impl SqliteTypeInfo for Color {
+///     const SQLITE_TYPE: &'static str = "TEXT";
+/// }
+/// ```
 pub trait SqliteTypeInfo {
-    fn sqlite_type() -> &'static str;
+    /// The declared sql type of a column holding this type.
+    const SQLITE_TYPE: &'static str;
+
+    /// Wether a column holding this type may be `NULL`.
+    const OPTIONAL: bool = false;
+
+    /// Returns [`SqliteTypeInfo::SQLITE_TYPE`]. Kept for callers of the method form.
+    fn sqlite_type() -> &'static str {
+        Self::SQLITE_TYPE
+    }
 
+    /// Returns [`SqliteTypeInfo::OPTIONAL`]. Kept for callers of the method form.
     fn optional() -> bool {
-        false
+        Self::OPTIONAL
     }
 }
 
@@ -91,58 +533,59 @@ mod types_impl {
 
     use super::*;
 
-    impl<T: SqliteTypeInfo + ToOwned + ?Sized> SqliteTypeInfo for std::borrow::Cow<'_, T> { fn sqlite_type() -> &'static str { T::sqlite_type() } }
-    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for Box<T> { fn sqlite_type() -> &'static str { T::sqlite_type() } }
-    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for std::rc::Rc<T> { fn sqlite_type() -> &'static str { T::sqlite_type() } }
-    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for std::sync::Arc<T> { fn sqlite_type() -> &'static str { T::sqlite_type() } }
+    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for &T { const SQLITE_TYPE: &'static str = T::SQLITE_TYPE; }
+    impl<T: SqliteTypeInfo + ToOwned + ?Sized> SqliteTypeInfo for std::borrow::Cow<'_, T> { const SQLITE_TYPE: &'static str = T::SQLITE_TYPE; }
+    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for Box<T> { const SQLITE_TYPE: &'static str = T::SQLITE_TYPE; }
+    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for std::rc::Rc<T> { const SQLITE_TYPE: &'static str = T::SQLITE_TYPE; }
+    impl<T: SqliteTypeInfo + ?Sized> SqliteTypeInfo for std::sync::Arc<T> { const SQLITE_TYPE: &'static str = T::SQLITE_TYPE; }
     impl<T: SqliteTypeInfo> SqliteTypeInfo for Option<T> {
-        fn sqlite_type() -> &'static str { T::sqlite_type() }
-        fn optional() -> bool { true }
+        const SQLITE_TYPE: &'static str = T::SQLITE_TYPE;
+        const OPTIONAL: bool = true;
     }
 
-    impl SqliteTypeInfo for Null { fn sqlite_type() -> &'static str { "NULL" } }
-    impl SqliteTypeInfo for bool { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for i8 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for i16 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for i32 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for i64 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for isize { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for u8 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for u16 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for u32 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for f32 { fn sqlite_type() -> &'static str { "REAL" } }
-    impl SqliteTypeInfo for f64 { fn sqlite_type() -> &'static str { "REAL" } }
-    impl SqliteTypeInfo for u64 { fn sqlite_type() -> &'static str { "INTEGER" } }
-    impl SqliteTypeInfo for usize { fn sqlite_type() -> &'static str { "INTEGER" } }
+    impl SqliteTypeInfo for Null { const SQLITE_TYPE: &'static str = "NULL"; }
+    impl SqliteTypeInfo for bool { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for i8 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for i16 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for i32 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for i64 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for isize { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for u8 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for u16 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for u32 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for f32 { const SQLITE_TYPE: &'static str = "REAL"; }
+    impl SqliteTypeInfo for f64 { const SQLITE_TYPE: &'static str = "REAL"; }
+    impl SqliteTypeInfo for u64 { const SQLITE_TYPE: &'static str = "INTEGER"; }
+    impl SqliteTypeInfo for usize { const SQLITE_TYPE: &'static str = "INTEGER"; }
 
-    impl SqliteTypeInfo for String { fn sqlite_type() -> &'static str { "TEXT" } }
-    impl SqliteTypeInfo for str { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for String { const SQLITE_TYPE: &'static str = "TEXT"; }
+    impl SqliteTypeInfo for str { const SQLITE_TYPE: &'static str = "TEXT"; }
 
-    impl SqliteTypeInfo for Vec<u8> { fn sqlite_type() -> &'static str { "BLOB" } }
-    impl<const N: usize> SqliteTypeInfo for [u8; N] { fn sqlite_type() -> &'static str { "BLOB" } }
-    impl SqliteTypeInfo for [u8] { fn sqlite_type() -> &'static str { "BLOB" } }
+    impl SqliteTypeInfo for Vec<u8> { const SQLITE_TYPE: &'static str = "BLOB"; }
+    impl<const N: usize> SqliteTypeInfo for [u8; N] { const SQLITE_TYPE: &'static str = "BLOB"; }
+    impl SqliteTypeInfo for [u8] { const SQLITE_TYPE: &'static str = "BLOB"; }
 
 
     #[cfg(feature = "serde")]
-    impl SqliteTypeInfo for serde_json::Value { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for serde_json::Value { const SQLITE_TYPE: &'static str = "TEXT"; }
 
     #[cfg(feature = "chrono")]
-    impl SqliteTypeInfo for chrono::NaiveDate { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for chrono::NaiveDate { const SQLITE_TYPE: &'static str = "TEXT"; }
     #[cfg(feature = "chrono")]
-    impl SqliteTypeInfo for chrono::NaiveTime { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for chrono::NaiveTime { const SQLITE_TYPE: &'static str = "TEXT"; }
     #[cfg(feature = "chrono")]
-    impl SqliteTypeInfo for chrono::NaiveDateTime { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for chrono::NaiveDateTime { const SQLITE_TYPE: &'static str = "TEXT"; }
     #[cfg(feature = "chrono")]
-    impl SqliteTypeInfo for chrono::DateTime<chrono::Utc> { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for chrono::DateTime<chrono::Utc> { const SQLITE_TYPE: &'static str = "TEXT"; }
     #[cfg(feature = "chrono")]
-    impl SqliteTypeInfo for chrono::DateTime<chrono::FixedOffset> { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for chrono::DateTime<chrono::FixedOffset> { const SQLITE_TYPE: &'static str = "TEXT"; }
 
     #[cfg(feature = "url")]
-    impl SqliteTypeInfo for url::Url { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for url::Url { const SQLITE_TYPE: &'static str = "TEXT"; }
 
     #[cfg(feature = "uuid")]
-    impl SqliteTypeInfo for uuid::Uuid { fn sqlite_type() -> &'static str { "TEXT" } }
+    impl SqliteTypeInfo for uuid::Uuid { const SQLITE_TYPE: &'static str = "TEXT"; }
 
-    // impl SqliteType for ZeroBlob { fn sqlite_type() -> &'static str { "BLOB" } }
+    // impl SqliteType for ZeroBlob { const SQLITE_TYPE: &'static str = "BLOB"; }
 
 }
@@ -0,0 +1,272 @@
+use std::marker::PhantomData;
+
+use crate::{quote_identifier, to_row::quote_identifiers, ToRow};
+
+/// Builds an `INSERT .. ON CONFLICT ..` statement for `T`, created by [`ToRow::upsert`].
+///
+/// By default the conflict target is the primary key (or the first declared unique
+/// constraint) and all other columns are updated with the inserted values. An
+/// `#[rusqlite(auto)]` key is not inserted and can't conflict, so for these tables the
+/// first unique constraint over inserted columns is the default. Column names are checked
+/// against [`ToRow::TABLE`], the first invalid one is returned by [`Upsert::to_sql`].
+///
+/// ```ignore
+/// let stmt = Post::upsert()
+///     .on_conflict(&["slug"])
+///     .update_columns(&["title", "updated_at"])
+///     .update_where("excluded.updated_at > updated_at")
+///     .to_sql()?;
+/// conn.execute(&stmt, post.to_params())?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Upsert<T> {
+    /// An error if the default target can't conflict, replaced by [`Upsert::on_conflict`].
+    conflict_target: Result<Vec<&'static str>, UpsertError>,
+    action: UpsertAction,
+    error: Option<UpsertError>,
+    _type: PhantomData<T>,
+}
+
+#[derive(Debug, Clone)]
+enum UpsertAction {
+    DoNothing,
+    DoUpdate {
+        columns: Option<Vec<&'static str>>,
+        condition: Option<String>,
+    },
+}
+
+impl<T: ToRow> Default for Upsert<T> {
+    fn default() -> Self {
+        let keys = T::TABLE.unique_keys();
+        let conflict_target = match keys.iter().find(|key| is_inserted::<T>(key)) {
+            Some(key) => Ok(key.clone()),
+            None => match keys.first() {
+                Some(key) => Err(UpsertError::NotInserted {
+                    table: T::TABLE.name,
+                    columns: key.iter().map(|name| name.to_string()).collect(),
+                }),
+                None => Ok(Vec::new()),
+            },
+        };
+        Self {
+            conflict_target,
+            action: UpsertAction::DoUpdate {
+                columns: None,
+                condition: None,
+            },
+            error: None,
+            _type: PhantomData,
+        }
+    }
+}
+
+/// Wether all `columns` are inserted, i.e. not an `auto` key or `skip_insert`.
+fn is_inserted<T: ToRow>(columns: &[&str]) -> bool {
+    columns
+        .iter()
+        .all(|name| T::insert_column_names().contains(name))
+}
+
+impl<T: ToRow> Upsert<T> {
+    /// Use `columns` as the conflict target. They have to be the primary key or a declared
+    /// unique constraint (column level `unique`, struct level `unique(..)` or a
+    /// `unique_index`), otherwise [`Upsert::to_sql`] returns [`UpsertError::NotUnique`].
+    /// Columns that are not inserted, like an `#[rusqlite(auto)]` key, never conflict and
+    /// return [`UpsertError::NotInserted`].
+    pub fn on_conflict(mut self, columns: &[&str]) -> Self {
+        match T::TABLE
+            .unique_keys()
+            .into_iter()
+            .find(|key| key.len() == columns.len() && key.iter().all(|c| columns.contains(c)))
+        {
+            Some(key) if is_inserted::<T>(&key) => self.conflict_target = Ok(key),
+            Some(key) => self.fail(UpsertError::NotInserted {
+                table: T::TABLE.name,
+                columns: key.iter().map(|name| name.to_string()).collect(),
+            }),
+            None => self.fail(UpsertError::NotUnique {
+                table: T::TABLE.name,
+                columns: columns.iter().map(|name| name.to_string()).collect(),
+            }),
+        }
+        self
+    }
+
+    /// Ignore the insert on conflict (`DO NOTHING`).
+    pub fn do_nothing(mut self) -> Self {
+        self.action = UpsertAction::DoNothing;
+        self
+    }
+
+    /// Only update `columns` on conflict instead of all columns that are not part of the
+    /// conflict target. If one of `columns` is not a column of the table,
+    /// [`Upsert::to_sql`] returns [`UpsertError::UnknownColumn`].
+    pub fn update_columns(mut self, columns: &[&str]) -> Self {
+        let mut known = Vec::with_capacity(columns.len());
+        for name in columns {
+            match T::TABLE.column(name) {
+                Some(column) => known.push(column.name),
+                None => self.fail(UpsertError::UnknownColumn {
+                    table: T::TABLE.name,
+                    column: name.to_string(),
+                }),
+            }
+        }
+        let columns = known;
+        self.action = match self.action {
+            UpsertAction::DoUpdate { condition, .. } => UpsertAction::DoUpdate {
+                columns: Some(columns),
+                condition,
+            },
+            UpsertAction::DoNothing => UpsertAction::DoUpdate {
+                columns: Some(columns),
+                condition: None,
+            },
+        };
+        self
+    }
+
+    /// Only update if `condition` holds, e.g. `excluded.updated_at > updated_at`.
+    pub fn update_where(mut self, condition: impl Into<String>) -> Self {
+        let condition = Some(condition.into());
+        self.action = match self.action {
+            UpsertAction::DoUpdate { columns, .. } => UpsertAction::DoUpdate { columns, condition },
+            UpsertAction::DoNothing => UpsertAction::DoUpdate {
+                columns: None,
+                condition,
+            },
+        };
+        self
+    }
+
+    /// Renders the statement. Use with [`ToRow::to_params`].
+    ///
+    /// Fails if a column passed to the builder is invalid, or with
+    /// [`UpsertError::NoColumnsToUpdate`] if the update would not set any column, e.g.
+    /// because all columns are part of the conflict target. Use [`Upsert::do_nothing`] for
+    /// those tables.
+    pub fn to_sql(&self) -> Result<String, UpsertError> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+        let conflict_target = self.conflict_target.clone()?;
+        // `DEFAULT VALUES` can't be followed by an upsert clause.
+        if T::insert_column_names().is_empty() {
+            return Err(UpsertError::NoColumnsToInsert {
+                table: T::TABLE.name,
+            });
+        }
+
+        let mut stmt = T::insert_stmt();
+        stmt.push_str(" ON CONFLICT");
+        if !conflict_target.is_empty() {
+            stmt.push_str(" (");
+            stmt.push_str(&quote_identifiers(&conflict_target));
+            stmt.push(')');
+        }
+
+        let (columns, condition) = match &self.action {
+            UpsertAction::DoNothing => {
+                stmt.push_str(" DO NOTHING");
+                return Ok(stmt);
+            }
+            UpsertAction::DoUpdate { columns, condition } => {
+                let columns = match columns {
+                    Some(columns) => columns.clone(),
+                    None => T::insert_column_names()
+                        .iter()
+                        .copied()
+                        .filter(|name| !conflict_target.contains(name))
+                        .collect(),
+                };
+                (columns, condition.as_deref())
+            }
+        };
+
+        if columns.is_empty() {
+            return Err(UpsertError::NoColumnsToUpdate {
+                table: T::TABLE.name,
+            });
+        }
+
+        stmt.push_str(" DO UPDATE SET ");
+        stmt.push_str(
+            &columns
+                .iter()
+                .map(|name| {
+                    let name = quote_identifier(name);
+                    format!("{name} = excluded.{name}")
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        if let Some(condition) = condition {
+            stmt.push_str(" WHERE ");
+            stmt.push_str(condition);
+        }
+        Ok(stmt)
+    }
+
+    /// Keeps the first error, it is returned by [`Upsert::to_sql`].
+    fn fail(&mut self, err: UpsertError) {
+        self.error.get_or_insert(err);
+    }
+}
+
+/// An invalid [`Upsert`], returned by [`Upsert::to_sql`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpsertError {
+    /// The conflict target is not the primary key or a unique constraint of the table.
+    NotUnique {
+        table: &'static str,
+        columns: Vec<String>,
+    },
+    /// A column to update is not a column of the table.
+    UnknownColumn { table: &'static str, column: String },
+    /// `DO UPDATE` without any column to update.
+    NoColumnsToUpdate { table: &'static str },
+    /// The conflict target contains columns that are not inserted, like an
+    /// `#[rusqlite(auto)]` key, so the insert never conflicts on it.
+    NotInserted {
+        table: &'static str,
+        columns: Vec<String>,
+    },
+    /// The table has no inserted columns, e.g. only an `#[rusqlite(auto)]` key.
+    NoColumnsToInsert { table: &'static str },
+}
+
+impl std::fmt::Display for UpsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotUnique { table, columns } => write!(
+                f,
+                "{columns:?} is not the primary key or a unique constraint of table `{table}`"
+            ),
+            Self::UnknownColumn { table, column } => {
+                write!(f, "`{column}` is not a column of table `{table}`")
+            }
+            Self::NoColumnsToUpdate { table } => write!(
+                f,
+                "upsert into table `{table}` has no columns to update, use `do_nothing`"
+            ),
+            Self::NotInserted { table, columns } => write!(
+                f,
+                "{columns:?} of table `{table}` is not inserted and can't conflict, use `on_conflict` with a unique constraint"
+            ),
+            Self::NoColumnsToInsert { table } => {
+                write!(f, "upsert into table `{table}` has no columns to insert")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UpsertError {}
+
+/// Turns the error into [`rusqlite::Error::ToSqlConversionFailure`], so it can be returned
+/// from functions running the statement.
+impl From<UpsertError> for rusqlite::Error {
+    fn from(err: UpsertError) -> Self {
+        rusqlite::Error::ToSqlConversionFailure(Box::new(err))
+    }
+}
@@ -1,5 +1,11 @@
+use std::{net::Ipv4Addr, time::Duration};
+
 use rusqlite::{params, Connection};
-use rusqlite_mapper::{FromRow, SqliteValue, ToRow};
+use rusqlite_mapper::{
+    prepare_checked, prepare_checked_strict, quote_identifier, Column, ColumnMismatch, ColumnName,
+    FromRow, FromRowId, MapperError, MapperExt, Prefix, Prefixed, PrepareError, RowPlan, Scalar,
+    SqliteValue, StatementExt, ToRow, UpsertError,
+};
 
 #[derive(Debug, FromRow)]
 #[allow(dead_code)]
@@ -117,7 +123,7 @@ fn from_row() {
 
 #[derive(Debug, FromRow, ToRow)]
 struct Person {
-    #[rusqlite(primary_key)]
+    #[rusqlite(primary_key, auto)]
     id: i32,
     name: String,
     role: PersonRole,
@@ -165,7 +171,7 @@ fn to_row() {
     conn.execute(dbg!(Person::create_table_statement().as_str()), ())
         .expect("Failed to create table");
 
-    let me = Person {
+    let mut me = Person {
         id: 0,
         role: PersonRole::Admin,
         ignore: IgnoreMe,
@@ -173,8 +179,26 @@ fn to_row() {
         data: None,
     };
 
-    conn.execute(dbg!(&Person::insert_stmt()), me.to_params())
+    assert_eq!(
+        Person::insert_stmt(),
+        r#"INSERT INTO "person" ("name", "role", "data") VALUES (?, ?, ?)"#
+    );
+
+    me.insert(&conn).expect("Failed to insert");
+    assert_eq!(me.id, 1);
+
+    let mut you = Person {
+        id: 0,
+        role: PersonRole::User,
+        ignore: IgnoreMe,
+        name: "Jane".to_string(),
+        data: None,
+    };
+    conn.execute(&Person::insert_stmt(), you.to_params())
         .expect("Failed to insert");
+    you.set_rowid(conn.last_insert_rowid())
+        .expect("Failed to set rowid");
+    assert_eq!(you.id, 2);
 
     let mut stmt = conn
         .prepare("SELECT * FROM person")
@@ -187,3 +211,2134 @@ fn to_row() {
         println!("Found person {:?}", person.unwrap());
     }
 }
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Wide {
+    c00: i64,
+    c01: String,
+    c02: Option<f64>,
+    c03: bool,
+    c04: i64,
+    c05: String,
+    c06: Option<f64>,
+    c07: bool,
+    c08: i64,
+    c09: String,
+    c10: Option<f64>,
+    c11: bool,
+    c12: i64,
+    c13: String,
+    c14: Option<f64>,
+    c15: bool,
+    c16: i64,
+    c17: String,
+    c18: Option<f64>,
+    c19: bool,
+    c20: i64,
+    c21: String,
+    c22: Option<f64>,
+    c23: bool,
+    c24: i64,
+    c25: String,
+    c26: Option<f64>,
+    c27: bool,
+    c28: i64,
+    c29: String,
+    c30: Option<f64>,
+    c31: bool,
+    c32: i64,
+    c33: String,
+    c34: Option<f64>,
+    c35: bool,
+    c36: i64,
+    c37: String,
+    c38: Option<f64>,
+    c39: bool,
+}
+
+#[test]
+fn to_row_wide() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Wide::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let wide = Wide {
+        c00: 0,
+        c01: "value 1".to_string(),
+        c02: Some(2.5),
+        c03: true,
+        c04: 4,
+        c05: "value 5".to_string(),
+        c06: Some(6.5),
+        c07: true,
+        c08: 8,
+        c09: "value 9".to_string(),
+        c10: Some(10.5),
+        c11: true,
+        c12: 12,
+        c13: "value 13".to_string(),
+        c14: Some(14.5),
+        c15: true,
+        c16: 16,
+        c17: "value 17".to_string(),
+        c18: Some(18.5),
+        c19: true,
+        c20: 20,
+        c21: "value 21".to_string(),
+        c22: Some(22.5),
+        c23: true,
+        c24: 24,
+        c25: "value 25".to_string(),
+        c26: Some(26.5),
+        c27: true,
+        c28: 28,
+        c29: "value 29".to_string(),
+        c30: Some(30.5),
+        c31: true,
+        c32: 32,
+        c33: "value 33".to_string(),
+        c34: Some(34.5),
+        c35: true,
+        c36: 36,
+        c37: "value 37".to_string(),
+        c38: Some(38.5),
+        c39: true,
+    };
+
+    conn.execute(&Wide::insert_stmt(), wide.to_params())
+        .expect("Failed to insert");
+
+    let loaded = conn
+        .query_row("SELECT * FROM wide", [], Wide::try_from_row)
+        .expect("Failed to query");
+
+    assert_eq!(Wide::column_names().len(), 40);
+    assert_eq!(loaded, wide);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+type UserId = i64;
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Account {
+    #[rusqlite(primary_key)]
+    id: UserId,
+    age: std::primitive::i32,
+    name: Box<str>,
+    nick: std::sync::Arc<str>,
+    avatar: Vec<u8>,
+    email: Option<String>,
+    active: std::primitive::bool,
+}
+
+#[test]
+fn to_row_any_to_sql_type() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Account::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let account = Account {
+        id: 7,
+        age: 42,
+        name: "Steven".into(),
+        nick: "steve".into(),
+        avatar: vec![1, 2, 3],
+        email: None,
+        active: true,
+    };
+
+    conn.execute(&Account::insert_stmt(), account.to_params())
+        .expect("Failed to insert");
+
+    let loaded = conn
+        .query_row("SELECT * FROM account", [], Account::try_from_row)
+        .expect("Failed to query");
+
+    assert_eq!(loaded, account);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Envelope<T>
+where
+    T: std::fmt::Debug,
+{
+    #[rusqlite(primary_key)]
+    id: i64,
+    payload: T,
+}
+
+#[derive(Debug, ToRow)]
+struct Borrowed<'a> {
+    #[rusqlite(primary_key)]
+    id: i64,
+    name: &'a str,
+    data: std::borrow::Cow<'a, [u8]>,
+}
+
+#[test]
+fn to_row_generics_and_lifetimes() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Envelope::<String>::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let envelope = Envelope {
+        id: 1,
+        payload: "hello".to_string(),
+    };
+
+    conn.execute(&Envelope::<String>::insert_stmt(), envelope.to_params())
+        .expect("Failed to insert");
+
+    let loaded = conn
+        .query_row(
+            "SELECT * FROM envelope",
+            [],
+            Envelope::<String>::try_from_row,
+        )
+        .expect("Failed to query");
+
+    assert_eq!(loaded, envelope);
+
+    conn.execute(&Borrowed::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let name = String::from("borrowed");
+    let borrowed = Borrowed {
+        id: 1,
+        name: &name,
+        data: std::borrow::Cow::Borrowed(&[1, 2, 3]),
+    };
+
+    conn.execute(&Borrowed::insert_stmt(), borrowed.to_params())
+        .expect("Failed to insert");
+
+    let (loaded_name, loaded_data): (String, Vec<u8>) = conn
+        .query_row("SELECT name, data FROM borrowed", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .expect("Failed to query");
+
+    assert_eq!(loaded_name, name);
+    assert_eq!(loaded_data, vec![1, 2, 3]);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+#[rusqlite(table = "people", schema = "aux", rename_all = "camelCase")]
+struct AuxPerson {
+    #[rusqlite(primary_key)]
+    person_id: i64,
+    first_name: String,
+    #[rusqlite(rename = "surname")]
+    last_name: String,
+}
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+#[rusqlite(rename_all = "SCREAMING_SNAKE_CASE")]
+struct Shouty {
+    some_value: i64,
+}
+
+#[test]
+fn struct_level_attributes() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute("ATTACH DATABASE ':memory:' AS aux", ())
+        .expect("Failed to attach database");
+
+    assert_eq!(AuxPerson::table_name(), "people");
+    assert_eq!(AuxPerson::schema_name(), Some("aux"));
+    assert_eq!(AuxPerson::qualified_table_name(), r#""aux"."people""#);
+    assert_eq!(
+        AuxPerson::column_names(),
+        &["personId", "firstName", "surname"]
+    );
+    assert_eq!(Shouty::column_names(), &["SOME_VALUE"]);
+
+    conn.execute(&AuxPerson::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let person = AuxPerson {
+        person_id: 1,
+        first_name: "Jane".to_string(),
+        last_name: "Doe".to_string(),
+    };
+
+    conn.execute(&AuxPerson::insert_stmt(), person.to_params())
+        .expect("Failed to insert");
+
+    let loaded = conn
+        .query_row(
+            "SELECT personId, firstName, surname FROM aux.people",
+            [],
+            AuxPerson::try_from_row,
+        )
+        .expect("Failed to query");
+
+    assert_eq!(loaded, person);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Order {
+    #[rusqlite(primary_key)]
+    index: i64,
+    from: String,
+    #[rusqlite(rename = "first name")]
+    first_name: String,
+    #[rusqlite(rename = "say \"hi\"")]
+    greeting: String,
+}
+
+#[test]
+fn quoted_identifiers() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(quote_identifier(r#"say "hi""#), r#""say ""hi""""#);
+
+    conn.execute(&Order::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let order = Order {
+        index: 1,
+        from: "shop".to_string(),
+        first_name: "Jane".to_string(),
+        greeting: "hello".to_string(),
+    };
+
+    conn.execute(&Order::insert_stmt(), order.to_params())
+        .expect("Failed to insert");
+    conn.execute(&Order::upsert().to_sql().unwrap(), order.to_params())
+        .expect("Failed to upsert");
+
+    let loaded = conn
+        .query_row(r#"SELECT * FROM "order""#, [], Order::try_from_row)
+        .expect("Failed to query");
+
+    assert_eq!(loaded, order);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// A blog post.
+#[derive(Debug, ToRow)]
+#[allow(dead_code)]
+struct Post {
+    /// The id of the post.
+    #[rusqlite(primary_key)]
+    id: i64,
+    /// The title
+    /// of the post.
+    title: String,
+    score: Option<f64>,
+}
+
+#[test]
+fn table_def() {
+    let table = Post::TABLE;
+
+    assert_eq!(table.name, "post");
+    assert_eq!(table.schema, None);
+    assert_eq!(table.doc, Some("A blog post."));
+    assert_eq!(table.columns.len(), 3);
+
+    let id = table.column("id").unwrap();
+    assert_eq!(id.sql_type, "INTEGER");
+    assert!(id.primary_key);
+    assert!(!id.nullable);
+    assert_eq!(id.doc, Some("The id of the post."));
+
+    let title = table.column("title").unwrap();
+    assert_eq!(title.sql_type, "TEXT");
+    assert!(!title.nullable);
+    assert_eq!(title.doc, Some("The title\nof the post."));
+
+    let score = table.column("score").unwrap();
+    assert_eq!(score.sql_type, "REAL");
+    assert!(score.nullable);
+    assert_eq!(score.doc, None);
+
+    assert_eq!(
+        table.primary_key().map(|c| c.name).collect::<Vec<_>>(),
+        vec!["id"]
+    );
+    assert_eq!(
+        Post::create_table_statement(),
+        r#"CREATE TABLE "post" ("id" INTEGER PRIMARY KEY, "title" TEXT NOT NULL, "score" REAL)"#
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+#[rusqlite(without_rowid, strict)]
+struct Membership {
+    #[rusqlite(primary_key)]
+    user_id: i64,
+    #[rusqlite(primary_key)]
+    group_id: i64,
+    role: String,
+}
+
+#[test]
+fn composite_primary_key() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Membership::create_table_statement(),
+        r#"CREATE TABLE "membership" ("user_id" INTEGER NOT NULL, "group_id" INTEGER NOT NULL, "role" TEXT NOT NULL, PRIMARY KEY ("user_id", "group_id")) WITHOUT ROWID, STRICT"#
+    );
+
+    conn.execute(&Membership::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut membership = Membership {
+        user_id: 1,
+        group_id: 2,
+        role: "member".to_string(),
+    };
+
+    conn.execute(&Membership::insert_stmt(), membership.to_params())
+        .expect("Failed to insert");
+
+    membership.role = "admin".to_string();
+    conn.execute(
+        &Membership::upsert().to_sql().unwrap(),
+        membership.to_params(),
+    )
+    .expect("Failed to upsert");
+
+    let loaded = conn
+        .query_row("SELECT * FROM membership", [], Membership::try_from_row)
+        .expect("Failed to query");
+
+    assert_eq!(loaded, membership);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, FromRow, ToRow)]
+#[rusqlite(unique(first_name, last_name), check = "length(first_name) > 0")]
+struct Customer {
+    #[rusqlite(primary_key, autoincrement)]
+    id: i64,
+    #[rusqlite(unique, collate = "NOCASE")]
+    email: String,
+    first_name: String,
+    last_name: String,
+    #[rusqlite(check = "age >= 0", sql_type = "INT")]
+    age: i64,
+    #[rusqlite(default = "'active'")]
+    status: Option<String>,
+}
+
+#[test]
+fn column_constraints() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Customer::create_table_statement(),
+        r#"CREATE TABLE "customer" ("id" INTEGER PRIMARY KEY AUTOINCREMENT, "email" TEXT NOT NULL UNIQUE COLLATE NOCASE, "first_name" TEXT NOT NULL, "last_name" TEXT NOT NULL, "age" INT NOT NULL CHECK (age >= 0), "status" TEXT DEFAULT 'active', UNIQUE ("first_name", "last_name"), CHECK (length(first_name) > 0))"#
+    );
+
+    conn.execute(&Customer::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    conn.execute(
+        "INSERT INTO customer (email, first_name, last_name, age) VALUES ('jane@example.com', 'Jane', 'Doe', 30)",
+        (),
+    )
+    .expect("Failed to insert");
+
+    let customer = conn
+        .query_row("SELECT * FROM customer", [], Customer::try_from_row)
+        .expect("Failed to query");
+    assert_eq!(customer.status.as_deref(), Some("active"));
+
+    let duplicate_email = Customer {
+        id: 2,
+        email: "JANE@example.com".to_string(),
+        first_name: "Jane".to_string(),
+        last_name: "Smith".to_string(),
+        age: 20,
+        status: None,
+    };
+    assert!(conn
+        .execute(&Customer::insert_stmt(), duplicate_email.to_params())
+        .is_err());
+
+    let duplicate_name = Customer {
+        id: 3,
+        email: "other@example.com".to_string(),
+        first_name: "Jane".to_string(),
+        last_name: "Doe".to_string(),
+        age: 20,
+        status: None,
+    };
+    assert!(conn
+        .execute(&Customer::insert_stmt(), duplicate_name.to_params())
+        .is_err());
+
+    let negative_age = Customer {
+        id: 4,
+        email: "young@example.com".to_string(),
+        first_name: "Benjamin".to_string(),
+        last_name: "Button".to_string(),
+        age: -1,
+        status: None,
+    };
+    assert!(conn
+        .execute(&Customer::insert_stmt(), negative_age.to_params())
+        .is_err());
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, ToRow)]
+struct Writer {
+    #[rusqlite(primary_key)]
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, ToRow)]
+struct Article {
+    #[rusqlite(primary_key)]
+    id: i64,
+    #[rusqlite(references(ty = "Writer", column = "id", on_delete = "cascade"))]
+    writer_id: i64,
+}
+
+#[derive(Debug, ToRow)]
+struct Comment {
+    #[rusqlite(primary_key)]
+    id: i64,
+    #[rusqlite(references(
+        table = "article",
+        on_delete = "set null",
+        on_update = "no action",
+        deferrable
+    ))]
+    article_id: Option<i64>,
+    #[rusqlite(references(ty = "Self", column = "id"))]
+    parent_id: Option<i64>,
+}
+
+#[test]
+fn foreign_keys() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+    conn.execute("PRAGMA foreign_keys = ON", ())
+        .expect("Failed to enable foreign keys");
+
+    assert_eq!(
+        Article::create_table_statement(),
+        r#"CREATE TABLE "article" ("id" INTEGER PRIMARY KEY, "writer_id" INTEGER NOT NULL REFERENCES "writer"("id") ON DELETE CASCADE)"#
+    );
+    assert_eq!(
+        Comment::create_table_statement(),
+        r#"CREATE TABLE "comment" ("id" INTEGER PRIMARY KEY, "article_id" INTEGER REFERENCES "article" ON DELETE SET NULL ON UPDATE NO ACTION DEFERRABLE INITIALLY DEFERRED, "parent_id" INTEGER REFERENCES "comment"("id"))"#
+    );
+
+    let tables =
+        rusqlite_mapper::dependency_order(&[Comment::TABLE, Article::TABLE, Writer::TABLE]);
+    assert_eq!(
+        tables.iter().map(|t| t.name).collect::<Vec<_>>(),
+        vec!["writer", "article", "comment"]
+    );
+
+    for table in &tables {
+        conn.execute(&table.create_table_statement(), ())
+            .expect("Failed to create table");
+    }
+
+    let writer = Writer {
+        id: 1,
+        name: "Jane".to_string(),
+    };
+    conn.execute(&Writer::insert_stmt(), writer.to_params())
+        .expect("Failed to insert");
+    let article = Article {
+        id: 1,
+        writer_id: writer.id,
+    };
+    conn.execute(&Article::insert_stmt(), article.to_params())
+        .expect("Failed to insert");
+    let comment = Comment {
+        id: 1,
+        article_id: Some(article.id),
+        parent_id: None,
+    };
+    conn.execute(&Comment::insert_stmt(), comment.to_params())
+        .expect("Failed to insert");
+
+    let dangling = Article {
+        id: 2,
+        writer_id: 42,
+    };
+    assert!(conn
+        .execute(&Article::insert_stmt(), dangling.to_params())
+        .is_err());
+
+    conn.execute("DELETE FROM writer", ())
+        .expect("Failed to delete");
+
+    let articles: i64 = conn
+        .query_row("SELECT count(*) FROM article", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(articles, 0);
+    let article_id: Option<i64> = conn
+        .query_row("SELECT article_id FROM comment", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(article_id, None);
+
+    for table in tables.iter().rev() {
+        conn.execute(&table.drop_table_statement(), ())
+            .expect("Failed to drop table");
+    }
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, ToRow)]
+#[rusqlite(
+    index(columns(last_name, first_name)),
+    index(name = "member_email_lower", columns("lower(email)"), unique),
+    index(name = "member_active", columns(created_at), where = "active = 1")
+)]
+struct Member {
+    #[rusqlite(primary_key)]
+    id: i64,
+    #[rusqlite(unique_index)]
+    handle: String,
+    #[rusqlite(index)]
+    email: String,
+    first_name: String,
+    last_name: String,
+    created_at: i64,
+    active: bool,
+}
+
+#[test]
+fn indexes() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Member::create_indexes_statements(),
+        vec![
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "idx_member_handle" ON "member" ("handle")"#,
+            r#"CREATE INDEX IF NOT EXISTS "idx_member_email" ON "member" ("email")"#,
+            r#"CREATE INDEX IF NOT EXISTS "idx_member_last_name_first_name" ON "member" ("last_name", "first_name")"#,
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "member_email_lower" ON "member" (lower(email))"#,
+            r#"CREATE INDEX IF NOT EXISTS "member_active" ON "member" ("created_at") WHERE active = 1"#,
+        ]
+    );
+
+    let statements = Member::create_schema_statements();
+    assert_eq!(statements.len(), 6);
+    assert_eq!(statements[0], Member::create_table_statement());
+    for stmt in &statements {
+        conn.execute(stmt, ()).expect("Failed to create schema");
+    }
+
+    let index_count: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'member' AND sql IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(index_count, 5);
+
+    let member = |id: i64, handle: &str, email: &str| Member {
+        id,
+        handle: handle.to_string(),
+        email: email.to_string(),
+        first_name: "Jane".to_string(),
+        last_name: "Doe".to_string(),
+        created_at: 0,
+        active: true,
+    };
+    conn.execute(
+        &Member::insert_stmt(),
+        member(1, "jane", "jane@example.com").to_params(),
+    )
+    .expect("Failed to insert");
+    assert!(conn
+        .execute(
+            &Member::insert_stmt(),
+            member(2, "jane2", "JANE@example.com").to_params()
+        )
+        .is_err());
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn crud_statements() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Account::update_stmt(),
+        r#"UPDATE "account" SET "age" = ?, "name" = ?, "nick" = ?, "avatar" = ?, "email" = ?, "active" = ? WHERE "id" = ?"#
+    );
+    assert_eq!(
+        Account::delete_stmt(),
+        r#"DELETE FROM "account" WHERE "id" = ?"#
+    );
+    assert_eq!(
+        Account::select_all_stmt(),
+        r#"SELECT "id", "age", "name", "nick", "avatar", "email", "active" FROM "account""#
+    );
+    assert_eq!(
+        Account::select_by_pk_stmt(),
+        r#"SELECT "id", "age", "name", "nick", "avatar", "email", "active" FROM "account" WHERE "id" = ?"#
+    );
+
+    conn.execute(&Account::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut account = Account {
+        id: 1,
+        age: 42,
+        name: "Steven".into(),
+        nick: "steve".into(),
+        avatar: vec![],
+        email: None,
+        active: true,
+    };
+    conn.execute(&Account::insert_stmt(), account.to_params())
+        .expect("Failed to insert");
+
+    account.email = Some("steven@example.com".to_string());
+    account.age = 43;
+    assert_eq!(
+        conn.execute(&Account::update_stmt(), account.to_update_params())
+            .expect("Failed to update"),
+        1
+    );
+
+    let loaded = conn
+        .query_row(
+            &Account::select_by_pk_stmt(),
+            account.pk_params(),
+            Account::try_from_row,
+        )
+        .expect("Failed to query");
+    assert_eq!(loaded, account);
+
+    assert_eq!(
+        conn.execute(&Account::delete_stmt(), account.pk_params())
+            .expect("Failed to delete"),
+        1
+    );
+    let mut stmt = conn.prepare(&Account::select_all_stmt()).unwrap();
+    assert_eq!(
+        stmt.query_map([], Account::try_from_row).unwrap().count(),
+        0
+    );
+}
+
+#[test]
+fn crud_statements_composite_key() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Membership::update_stmt(),
+        r#"UPDATE "membership" SET "role" = ? WHERE "user_id" = ? AND "group_id" = ?"#
+    );
+
+    conn.execute(&Membership::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut membership = Membership {
+        user_id: 1,
+        group_id: 2,
+        role: "member".to_string(),
+    };
+    conn.execute(&Membership::insert_stmt(), membership.to_params())
+        .expect("Failed to insert");
+
+    membership.role = "admin".to_string();
+    conn.execute(&Membership::update_stmt(), membership.to_update_params())
+        .expect("Failed to update");
+
+    let loaded = conn
+        .query_row(
+            &Membership::select_by_pk_stmt(),
+            membership.pk_params(),
+            Membership::try_from_row,
+        )
+        .expect("Failed to query");
+    assert_eq!(loaded, membership);
+
+    assert_eq!(
+        conn.execute(&Membership::delete_stmt(), membership.pk_params())
+            .expect("Failed to delete"),
+        1
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Page {
+    #[rusqlite(primary_key)]
+    id: i64,
+    #[rusqlite(unique)]
+    slug: String,
+    title: String,
+    updated_at: i64,
+}
+
+#[test]
+fn upsert() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Page::upsert().to_sql().unwrap(),
+        r#"INSERT INTO "page" ("id", "slug", "title", "updated_at") VALUES (?, ?, ?, ?) ON CONFLICT ("id") DO UPDATE SET "slug" = excluded."slug", "title" = excluded."title", "updated_at" = excluded."updated_at""#
+    );
+    #[allow(deprecated)]
+    let legacy = Page::upsert_stmt("slug");
+    assert_eq!(
+        legacy,
+        r#"INSERT INTO "page" ("id", "slug", "title", "updated_at") VALUES (?, ?, ?, ?) ON CONFLICT (slug) DO UPDATE SET "title" = excluded."title", "updated_at" = excluded."updated_at""#
+    );
+    assert_eq!(
+        Page::upsert()
+            .on_conflict(&["slug"])
+            .do_nothing()
+            .to_sql()
+            .unwrap(),
+        r#"INSERT INTO "page" ("id", "slug", "title", "updated_at") VALUES (?, ?, ?, ?) ON CONFLICT ("slug") DO NOTHING"#
+    );
+    assert_eq!(
+        Page::upsert().update_columns(&["titel"]).to_sql(),
+        Err(UpsertError::UnknownColumn {
+            table: "page",
+            column: "titel".to_string(),
+        })
+    );
+    assert_eq!(
+        Page::upsert()
+            .on_conflict(&["slug"])
+            .update_columns(&[])
+            .update_where("excluded.updated_at > updated_at")
+            .to_sql(),
+        Err(UpsertError::NoColumnsToUpdate { table: "page" })
+    );
+    assert_eq!(
+        Page::insert_or_replace_stmt(),
+        r#"INSERT OR REPLACE INTO "page" ("id", "slug", "title", "updated_at") VALUES (?, ?, ?, ?)"#
+    );
+
+    conn.execute(&Page::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let page = |title: &str, updated_at: i64| Page {
+        id: 1,
+        slug: "home".to_string(),
+        title: title.to_string(),
+        updated_at,
+    };
+    let load = || {
+        conn.query_row("SELECT * FROM page", [], Page::try_from_row)
+            .expect("Failed to query")
+    };
+
+    conn.execute(&Page::insert_stmt(), page("first", 1).to_params())
+        .expect("Failed to insert");
+
+    conn.execute(
+        &Page::insert_or_ignore_stmt(),
+        page("ignored", 2).to_params(),
+    )
+    .expect("Failed to insert or ignore");
+    assert_eq!(load(), page("first", 1));
+
+    let guarded = Page::upsert()
+        .on_conflict(&["slug"])
+        .update_columns(&["title", "updated_at"])
+        .update_where("excluded.updated_at > updated_at")
+        .to_sql()
+        .unwrap();
+    assert!(guarded.ends_with(
+        r#"ON CONFLICT ("slug") DO UPDATE SET "title" = excluded."title", "updated_at" = excluded."updated_at" WHERE excluded.updated_at > updated_at"#
+    ));
+
+    conn.execute(&guarded, page("newer", 3).to_params())
+        .expect("Failed to upsert");
+    assert_eq!(load(), page("newer", 3));
+
+    conn.execute(&guarded, page("older", 2).to_params())
+        .expect("Failed to upsert");
+    assert_eq!(load(), page("newer", 3));
+
+    conn.execute(
+        &Page::upsert().to_sql().unwrap(),
+        page("upserted", 0).to_params(),
+    )
+    .expect("Failed to upsert");
+    assert_eq!(load(), page("upserted", 0));
+
+    conn.execute(
+        &Page::insert_or_replace_stmt(),
+        page("replaced", 5).to_params(),
+    )
+    .expect("Failed to insert or replace");
+    assert_eq!(load(), page("replaced", 5));
+}
+
+#[test]
+fn upsert_invalid_conflict_target() {
+    let err = Page::upsert().on_conflict(&["title"]).to_sql().unwrap_err();
+    assert_eq!(
+        err,
+        UpsertError::NotUnique {
+            table: "page",
+            columns: vec!["title".to_string()],
+        }
+    );
+    assert!(err
+        .to_string()
+        .contains("is not the primary key or a unique constraint"));
+}
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Subscriber {
+    #[rusqlite(primary_key, auto)]
+    id: i64,
+    #[rusqlite(unique)]
+    email: String,
+    name: String,
+}
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Counter {
+    #[rusqlite(primary_key, auto)]
+    id: i64,
+}
+
+#[test]
+fn upsert_auto_key() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Subscriber::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    // The key is not inserted, the upsert conflicts on `email` instead.
+    assert_eq!(
+        Subscriber::upsert().to_sql().unwrap(),
+        r#"INSERT INTO "subscriber" ("email", "name") VALUES (?, ?) ON CONFLICT ("email") DO UPDATE SET "name" = excluded."name""#
+    );
+    let mut account = Subscriber {
+        id: 0,
+        email: "jane@example.com".to_string(),
+        name: "Jane".to_string(),
+    };
+    conn.insert(&mut account).unwrap();
+    account.name = "Jane Doe".to_string();
+    assert_eq!(conn.upsert(&account).unwrap(), 1);
+    assert_eq!(conn.find_all::<Subscriber>().unwrap(), [account]);
+
+    assert_eq!(
+        Subscriber::upsert().on_conflict(&["id"]).to_sql(),
+        Err(UpsertError::NotInserted {
+            table: "subscriber",
+            columns: vec!["id".to_string()],
+        })
+    );
+    assert_eq!(
+        Note::upsert().to_sql(),
+        Err(UpsertError::NotInserted {
+            table: "note",
+            columns: vec!["id".to_string()],
+        })
+    );
+
+    // Without inserted columns the insert uses the sql defaults.
+    conn.execute(&Counter::create_table_statement(), ())
+        .expect("Failed to create table");
+    assert_eq!(
+        Counter::insert_stmt(),
+        r#"INSERT INTO "counter" DEFAULT VALUES"#
+    );
+    let mut counter = Counter { id: 0 };
+    conn.insert(&mut counter).unwrap();
+    conn.insert(&mut counter).unwrap();
+    assert_eq!(counter.id, 2);
+    assert_eq!(
+        Counter { id: 0 }.insert_returning(&conn).unwrap(),
+        Counter { id: 3 }
+    );
+    assert!(Counter::upsert().to_sql().is_err());
+    assert_eq!(
+        Counter::upsert().do_nothing().to_sql(),
+        Err(UpsertError::NotInserted {
+            table: "counter",
+            columns: vec!["id".to_string()],
+        })
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Note {
+    #[rusqlite(primary_key, auto)]
+    id: Option<i64>,
+    text: String,
+}
+
+#[test]
+fn auto_key_option() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Note::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut note = Note {
+        id: None,
+        text: "hello".to_string(),
+    };
+    note.insert(&conn).expect("Failed to insert");
+    assert_eq!(note.id, Some(1));
+
+    let loaded = conn
+        .query_row(
+            &Note::select_by_pk_stmt(),
+            note.pk_params(),
+            Note::try_from_row,
+        )
+        .expect("Failed to query");
+    assert_eq!(loaded, note);
+
+    let err = <Option<u32> as FromRowId>::from_row_id(-1).unwrap_err();
+    assert!(
+        matches!(&err, rusqlite::Error::ToSqlConversionFailure(err) if err.to_string() == "rowid -1 is out of range for `u32`"),
+        "{err:?}"
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(ToRow, FromRow, Debug, PartialEq)]
+struct Ticket {
+    #[rusqlite(primary_key, auto)]
+    id: i64,
+    title: String,
+    #[rusqlite(skip_insert, default = "'open'")]
+    status: String,
+}
+
+#[test]
+fn returning() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Ticket::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    assert_eq!(
+        Ticket::insert_returning_stmt(),
+        r#"INSERT INTO "ticket" ("title") VALUES (?) RETURNING "id", "title", "status""#
+    );
+    assert_eq!(
+        Ticket::update_returning_stmt(),
+        r#"UPDATE "ticket" SET "title" = ?, "status" = ? WHERE "id" = ? RETURNING "id", "title", "status""#
+    );
+    assert_eq!(
+        Ticket::delete_returning_stmt(),
+        r#"DELETE FROM "ticket" WHERE "id" = ? RETURNING "id", "title", "status""#
+    );
+
+    let ticket = Ticket {
+        id: 0,
+        title: "broken build".to_string(),
+        status: String::new(),
+    }
+    .insert_returning(&conn)
+    .expect("Failed to insert");
+    assert_eq!(
+        ticket,
+        Ticket {
+            id: 1,
+            title: "broken build".to_string(),
+            status: "open".to_string(),
+        }
+    );
+
+    let closed = Ticket {
+        status: "closed".to_string(),
+        ..ticket
+    }
+    .update_returning(&conn)
+    .expect("Failed to update");
+    assert_eq!(closed.status, "closed");
+
+    let missing = Ticket { id: 42, ..closed }.update_returning(&conn);
+    assert!(matches!(missing, Err(rusqlite::Error::QueryReturnedNoRows)));
+
+    let deleted = conn
+        .query_row(&Ticket::delete_returning_stmt(), [1], Ticket::try_from_row)
+        .expect("Failed to delete");
+    assert_eq!(deleted.title, "broken build");
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn mapper_ext() {
+    let mut conn =
+        rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute(&Note::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut first = Note {
+        id: None,
+        text: "first".to_string(),
+    };
+    conn.insert(&mut first).expect("Failed to insert");
+    assert_eq!(first.id, Some(1));
+
+    let mut more = vec![
+        Note {
+            id: None,
+            text: "second".to_string(),
+        },
+        Note {
+            id: None,
+            text: "third".to_string(),
+        },
+    ];
+    let tx = conn.transaction().expect("Failed to begin transaction");
+    assert_eq!(tx.insert_many(&mut more).expect("Failed to insert"), 2);
+    tx.commit().expect("Failed to commit");
+    assert_eq!(more[0].id, Some(2));
+    assert_eq!(more[1].id, Some(3));
+
+    assert_eq!(conn.count::<Note>().unwrap(), 3);
+    assert!(conn.exists::<Note, _>([2]).unwrap());
+    assert!(!conn.exists::<Note, _>([4]).unwrap());
+
+    first.text = "updated".to_string();
+    assert_eq!(conn.update(&first).unwrap(), 1);
+    assert_eq!(conn.get::<Note, _>([1]).unwrap(), Some(first));
+    assert_eq!(conn.get::<Note, _>([4]).unwrap(), None);
+
+    assert_eq!(conn.delete(&more[0]).unwrap(), 1);
+    assert_eq!(conn.delete(&more[0]).unwrap(), 0);
+
+    assert_eq!(
+        conn.find_all::<Note>().unwrap(),
+        vec![
+            Note {
+                id: Some(1),
+                text: "updated".to_string(),
+            },
+            Note {
+                id: Some(3),
+                text: "third".to_string(),
+            },
+        ]
+    );
+
+    conn.execute(&Membership::create_table_statement(), ())
+        .expect("Failed to create table");
+    let mut membership = Membership {
+        user_id: 1,
+        group_id: 2,
+        role: "member".to_string(),
+    };
+    assert_eq!(conn.upsert(&membership).unwrap(), 1);
+    membership.role = "admin".to_string();
+    assert_eq!(conn.upsert(&membership).unwrap(), 1);
+    assert_eq!(conn.count::<Membership>().unwrap(), 1);
+    assert!(conn.exists::<Membership, _>([1, 2]).unwrap());
+    assert_eq!(conn.get::<Membership, _>([1, 2]).unwrap(), Some(membership));
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn query_helpers() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE role (id INTEGER PRIMARY KEY, kind TEXT NOT NULL);
+        CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, role_id INTEGER NULL);
+        INSERT INTO role (id, kind) VALUES (1, 'admin');
+        INSERT INTO user (id, name, role_id) VALUES (1, 'john', 1), (2, 'jack', NULL);
+        ",
+    )
+    .expect("Failed to create tables");
+
+    let sql = "
+        SELECT u.id, u.name, r.id AS role_id, r.kind AS role_kind
+        FROM user u LEFT JOIN role r ON r.id = u.role_id
+        WHERE u.id >= ?1
+        ORDER BY u.id";
+
+    let users = conn.query_as::<User, _>(sql, [1]).unwrap();
+    assert_eq!(users.len(), 2);
+    assert_eq!(
+        users[0].role.as_ref().map(|role| role.kind.as_str()),
+        Some("admin")
+    );
+    assert!(users[1].role.is_none());
+
+    let user: User = conn.query_one_as(sql, [2]).unwrap();
+    assert_eq!(user.name, "jack");
+    assert!(matches!(
+        conn.query_one_as::<User, _>(sql, [3]),
+        Err(rusqlite::Error::QueryReturnedNoRows)
+    ));
+
+    assert_eq!(
+        conn.query_opt_as::<User, _>(sql, [2])
+            .unwrap()
+            .map(|user| user.id),
+        Some(2)
+    );
+    assert!(conn.query_opt_as::<User, _>(sql, [3]).unwrap().is_none());
+
+    let names = conn
+        .query_iter_as(sql, [1], |users| {
+            users
+                .map(|user| user.map(|user: User| user.name))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap();
+    assert_eq!(names, ["john", "jack"]);
+
+    let mut stmt = conn.prepare(sql).unwrap();
+    let mut users = stmt.query_iter_as::<User, _>([1]).unwrap();
+    assert_eq!(users.next().unwrap().unwrap().name, "john");
+    assert_eq!(users.next().unwrap().unwrap().name, "jack");
+    assert!(users.next().is_none());
+    drop(users);
+    assert_eq!(stmt.query_as::<User, _>([2]).unwrap().len(), 1);
+    assert_eq!(stmt.query_one_as::<User, _>([1]).unwrap().name, "john");
+    assert!(stmt.query_opt_as::<User, _>([3]).unwrap().is_none());
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn row_plan() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    let mut stmt = conn
+        .prepare(
+            "
+            SELECT 'x' AS unused, 'admin' AS ROLE_KIND, 'john' AS name, 1 AS role_id, 7 AS id
+            UNION ALL
+            SELECT 'y', NULL, 'jack', NULL, 8",
+        )
+        .unwrap();
+
+    let plan = RowPlan::<User>::new(&stmt).unwrap();
+    assert_eq!(plan.indices(), [Some(4), Some(2), Some(3), Some(1)]);
+
+    let users = stmt
+        .query_map([], |row| {
+            let user = plan.try_from_row(row)?;
+            // The name based methods resolve the columns themselves.
+            assert_eq!(User::try_from_row(row)?.id, user.id);
+            Ok(user)
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(users[0].id, 7);
+    assert_eq!(users[0].name, "john");
+    assert_eq!(users[0].role.as_ref().map(|role| role.id), Some(1));
+    assert_eq!(users[1].id, 8);
+    assert!(users[1].role.is_none());
+
+    let role_plan = RowPlan::<Role>::prefixed(&stmt, Some("role_")).unwrap();
+    assert_eq!(role_plan.indices(), [Some(3), Some(1)]);
+
+    let err = RowPlan::<Todo>::new(&stmt).unwrap_err();
+    assert!(matches!(err, rusqlite::Error::InvalidColumnName(name) if name == "text"));
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// Implemented by hand with only the name based methods.
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+impl FromRow for Point {
+    fn try_from_row_prefixed(
+        row: &rusqlite::Row,
+        prefix: Option<&str>,
+    ) -> Result<Self, rusqlite::Error> {
+        let prefix = prefix.unwrap_or("");
+        Ok(Self {
+            x: row.get(format!("{prefix}x").as_str())?,
+            y: row.get(format!("{prefix}y").as_str())?,
+        })
+    }
+
+    fn is_all_null(row: &rusqlite::Row, prefix: Option<&str>) -> Result<bool, rusqlite::Error> {
+        let prefix = prefix.unwrap_or("");
+        Ok(
+            row.get_ref(format!("{prefix}x").as_str())? == rusqlite::types::ValueRef::Null
+                && row.get_ref(format!("{prefix}y").as_str())? == rusqlite::types::ValueRef::Null,
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Segment {
+    id: i64,
+    #[rusqlite(flatten, prefix = "from_")]
+    from: Point,
+    #[rusqlite(flatten, prefix = "to_")]
+    to: Option<Point>,
+}
+
+#[test]
+fn hand_written_from_row() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    let sql = "
+        SELECT 1 AS id, 0 AS from_x, 0 AS from_y, 3 AS to_x, 4 AS to_y
+        UNION ALL
+        SELECT 2, 5, 6, NULL, NULL";
+
+    let segments: Vec<Segment> = conn.query_as(sql, []).unwrap();
+    assert_eq!(
+        segments,
+        [
+            Segment {
+                id: 1,
+                from: Point { x: 0, y: 0 },
+                to: Some(Point { x: 3, y: 4 }),
+            },
+            Segment {
+                id: 2,
+                from: Point { x: 5, y: 6 },
+                to: None,
+            },
+        ]
+    );
+
+    let segment = conn.query_row(sql, [], Segment::try_from_row).unwrap();
+    assert_eq!(segment, segments[0]);
+
+    let points: Vec<Option<Prefixed<Point, To>>> = conn.query_as(sql, []).unwrap();
+    assert_eq!(points[0].as_deref(), Some(&Point { x: 3, y: 4 }));
+    assert!(points[1].is_none());
+}
+
+struct To;
+
+impl Prefix for To {
+    const PREFIX: &'static str = "to_";
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, PartialEq, FromRow)]
+#[rusqlite(by_index)]
+struct Stats {
+    count: i64,
+    max: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+#[rusqlite(by_index)]
+struct Report {
+    label: String,
+    #[rusqlite(flatten)]
+    stats: Stats,
+    #[rusqlite(flatten)]
+    previous: Option<Stats>,
+    #[rusqlite(position = 0)]
+    label_again: String,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct NamedReport {
+    #[rusqlite(position = 0)]
+    label: String,
+    total: i64,
+    // Continues after `total`.
+    #[rusqlite(flatten)]
+    stats: Stats,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+#[rusqlite(by_index)]
+struct Labelled {
+    label: String,
+    count: i64,
+    #[rusqlite(position = 0)]
+    label_again: String,
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+#[rusqlite(by_index)]
+struct LabelledTotal {
+    #[rusqlite(flatten)]
+    labelled: Labelled,
+    // Continues after the span of `labelled`, not after `label_again`.
+    total: i64,
+}
+
+#[test]
+fn by_index() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(Stats::COLUMN_COUNT, 2);
+    assert_eq!(Report::COLUMN_COUNT, 5);
+    assert_eq!(NamedReport::COLUMN_COUNT, 4);
+    assert_eq!(Labelled::COLUMN_COUNT, 2);
+    assert_eq!(LabelledTotal::COLUMN_COUNT, 3);
+
+    conn.execute_batch(
+        "
+        CREATE TABLE x (v INTEGER NOT NULL);
+        INSERT INTO x (v) VALUES (3), (5);
+        ",
+    )
+    .unwrap();
+
+    let report: Report = conn
+        .query_one_as("SELECT 'x', count(*), max(v), NULL, NULL FROM x", [])
+        .unwrap();
+    assert_eq!(
+        report,
+        Report {
+            label: "x".to_string(),
+            stats: Stats {
+                count: 2,
+                max: Some(5),
+            },
+            previous: None,
+            label_again: "x".to_string(),
+        }
+    );
+
+    let report: NamedReport = conn
+        .query_one_as("SELECT 'x', count(*) AS total, count(*), max(v) FROM x", [])
+        .unwrap();
+    assert_eq!(report.label, "x");
+    assert_eq!(report.total, 2);
+    assert_eq!(report.stats.max, Some(5));
+
+    let err = conn
+        .query_one_as::<Report, _>("SELECT 'x', count(*), max(v) FROM x", [])
+        .unwrap_err();
+    assert!(matches!(err, rusqlite::Error::InvalidColumnIndex(3)));
+
+    let labelled = Labelled {
+        label: "x".to_string(),
+        count: 7,
+        label_again: "x".to_string(),
+    };
+    let total: LabelledTotal = conn.query_one_as("SELECT 'x', 7, 99", []).unwrap();
+    assert_eq!(total.labelled, labelled);
+    assert_eq!(total.total, 99);
+
+    let (first, Scalar(total)): (Labelled, Scalar<i64>) =
+        conn.query_one_as("SELECT 'x', 7, 99", []).unwrap();
+    assert_eq!(first, labelled);
+    assert_eq!(total, 99);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+struct Admin;
+
+impl Prefix for Admin {
+    const PREFIX: &'static str = "admin_";
+}
+
+struct Total;
+
+impl ColumnName for Total {
+    const NAME: &'static str = "total";
+}
+
+#[test]
+fn tuples_and_scalars() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE role (id INTEGER PRIMARY KEY, kind TEXT NOT NULL);
+        CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, role_id INTEGER NULL);
+        INSERT INTO role (id, kind) VALUES (1, 'admin'), (2, 'guest');
+        INSERT INTO user (id, name, role_id) VALUES (1, 'john', 1), (2, 'jack', NULL);
+        ",
+    )
+    .expect("Failed to create tables");
+
+    assert_eq!(<(User, Role)>::COLUMN_COUNT, 6);
+
+    // Both elements have an `id` and a `kind`, each is looked up in its own range.
+    let pairs: Vec<(User, Role)> = conn
+        .query_as(
+            "
+            SELECT u.id, u.name, ur.id AS role_id, ur.kind AS role_kind, r.id, r.kind
+            FROM user u LEFT JOIN role ur ON ur.id = u.role_id JOIN role r
+            ORDER BY u.id, r.id",
+            [],
+        )
+        .unwrap();
+    assert_eq!(pairs.len(), 4);
+    assert_eq!(pairs[0].0.id, 1);
+    assert_eq!(pairs[0].1.kind, "admin");
+    assert_eq!(pairs[1].1.kind, "guest");
+    assert!(pairs[2].0.role.is_none());
+    assert_eq!(pairs[3].1.id, 2);
+
+    let ids: Vec<Scalar<i64>> = conn.query_as("SELECT id FROM user", []).unwrap();
+    assert_eq!(ids, [Scalar(1), Scalar(2)]);
+
+    let (count, max): (Scalar<i64>, Scalar<String>) = conn
+        .query_one_as("SELECT count(*), max(name) FROM user", [])
+        .unwrap();
+    assert_eq!(*count, 2);
+    assert_eq!(max.into_inner(), "john");
+
+    let kinds: Vec<(Scalar<String>, Option<Scalar<String>>)> = conn
+        .query_as(
+            "SELECT u.name, r.kind FROM user u LEFT JOIN role r ON r.id = u.role_id ORDER BY u.id",
+            [],
+        )
+        .unwrap();
+    assert_eq!(kinds[0].1, Some(Scalar("admin".to_string())));
+    assert_eq!(kinds[1].1, None);
+
+    let (role, admin): (Role, Prefixed<Role, Admin>) = conn
+        .query_one_as(
+            "SELECT r.id, r.kind, a.kind AS admin_kind, a.id AS admin_id
+            FROM role r JOIN role a ON a.id = 1 WHERE r.id = 2",
+            [],
+        )
+        .unwrap();
+    assert_eq!(role.kind, "guest");
+    assert_eq!(admin.kind, "admin");
+
+    let (total, Scalar(name)): (Column<i64, Total>, Scalar<String>) = conn
+        .query_one_as("SELECT count(*) AS total, max(name) FROM user", [])
+        .unwrap();
+    assert_eq!(*total, 2);
+    assert_eq!(name, "john");
+    let total = conn
+        .query_row("SELECT 1 AS admin_total", [], |row| {
+            Prefixed::<Column<i64, Total>, Admin>::try_from_row(row)
+        })
+        .unwrap();
+    assert_eq!(total.into_inner().into_inner(), 1);
+}
+
+#[derive(Debug, FromRow)]
+struct NameTotal {
+    name: String,
+    #[rusqlite(flatten)]
+    total: Option<Scalar<i64>>,
+}
+
+#[test]
+fn flatten_scalar_by_name() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+    let sql = "SELECT 'john' AS name, 42 AS total";
+
+    // Single rows read the scalar after `name` like a plan does.
+    const { assert!(NameTotal::BY_POSITION) };
+    let single = conn.query_row(sql, [], NameTotal::try_from_row).unwrap();
+    let planned: NameTotal = conn.query_one_as(sql, []).unwrap();
+    assert_eq!(single.name, "john");
+    assert_eq!(single.total, Some(Scalar(42)));
+    assert_eq!(planned.total, single.total);
+
+    let empty = conn
+        .query_row(
+            "SELECT 'john' AS name, NULL AS total",
+            [],
+            NameTotal::try_from_row,
+        )
+        .unwrap();
+    assert_eq!(empty.total, None);
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, FromRow)]
+struct Assignee {
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, FromRow)]
+struct Assignment {
+    id: i64,
+    text: String,
+    #[rusqlite(flatten, prefix = "author_")]
+    author: Assignee,
+    #[rusqlite(flatten, prefix = "editor_")]
+    editor: Option<Assignee>,
+}
+
+#[test]
+fn columns_and_select_list() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(Role::columns(None), ["id", "kind"]);
+    assert_eq!(
+        User::columns(Some("author_")),
+        [
+            "author_id",
+            "author_name",
+            "author_role_id",
+            "author_role_kind"
+        ]
+    );
+    assert_eq!(Todo::columns(None).len(), Todo::COLUMN_COUNT);
+    assert_eq!(Todo::columns(None)[6], "editor_id");
+    assert_eq!(<(Role, Scalar<i64>)>::columns(None), ["id", "kind"]);
+    assert_eq!(
+        Prefixed::<Column<i64, Total>, Admin>::columns(None),
+        ["admin_total"]
+    );
+    assert_eq!(Stats::columns(None), ["count", "max"]);
+
+    assert_eq!(
+        Assignee::select_list("a", "author_"),
+        r#"a."id" AS "author_id", a."name" AS "author_name""#
+    );
+    assert_eq!(
+        Role::select_list("", ""),
+        r#""id" AS "id", "kind" AS "kind""#
+    );
+    assert_eq!(
+        <(Role, Scalar<i64>)>::select_list("r", ""),
+        r#"r."id" AS "id", r."kind" AS "kind""#
+    );
+
+    conn.execute_batch(
+        "
+        CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+        CREATE TABLE todo (
+            id INTEGER PRIMARY KEY,
+            text TEXT NOT NULL,
+            author_id INTEGER NOT NULL,
+            editor_id INTEGER NULL
+        );
+        INSERT INTO user (id, name) VALUES (1, 'john'), (2, 'jack');
+        INSERT INTO todo (id, text, author_id, editor_id) VALUES (1, 'laundry', 1, 2), (2, 'dishes', 2, NULL);
+        ",
+    )
+    .expect("Failed to create tables");
+
+    let sql = format!(
+        "
+        SELECT t.id, t.text, {}, {}
+        FROM todo t
+        JOIN user a ON a.id = t.author_id
+        LEFT JOIN user e ON e.id = t.editor_id
+        ORDER BY t.id",
+        Assignee::select_list("a", "author_"),
+        Assignee::select_list("e", "editor_"),
+    );
+    let todos: Vec<Assignment> = conn.query_as(&sql, []).unwrap();
+    assert_eq!(todos[0].author.name, "john");
+    assert_eq!(todos[0].editor.as_ref().map(|editor| editor.id), Some(2));
+    assert_eq!(todos[1].id, 2);
+    assert_eq!(todos[1].text, "dishes");
+    assert_eq!(todos[1].author.name, "jack");
+    assert!(todos[1].editor.is_none());
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn prepare_checked_columns() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    conn.execute_batch(
+        "
+        CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT);
+        INSERT INTO user (id, name) VALUES (1, 'john');
+        ",
+    )
+    .expect("Failed to create table");
+
+    let mut stmt = prepare_checked::<Assignee>(&conn, "SELECT id, name, email FROM user")
+        .expect("Extra columns are allowed");
+    assert_eq!(stmt.query_as::<Assignee, _>([]).unwrap()[0].name, "john");
+
+    let err =
+        prepare_checked_strict::<Assignee>(&conn, "SELECT id, name, email FROM user").unwrap_err();
+    let PrepareError::Columns(mismatch) = err else {
+        panic!("expected a column mismatch, got {err:?}");
+    };
+    assert!(mismatch.missing.is_empty());
+    assert_eq!(mismatch.extra, ["email"]);
+
+    let err =
+        prepare_checked::<Assignee>(&conn, "SELECT id, name AS nmae, email FROM user").unwrap_err();
+    let PrepareError::Columns(mismatch) = &err else {
+        panic!("expected a column mismatch, got {err:?}");
+    };
+    assert_eq!(
+        mismatch,
+        &ColumnMismatch {
+            type_name: std::any::type_name::<Assignee>(),
+            missing: vec!["name".to_string()],
+            extra: vec![],
+            suggestions: vec![("name".to_string(), "nmae".to_string())],
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "columns of `{}` don't match the statement: missing `name` (did you mean `nmae`?)",
+            std::any::type_name::<Assignee>()
+        )
+    );
+
+    let err = prepare_checked_strict::<Assignee>(&conn, "SELECT id, name AS nmae, email FROM user")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "columns of `{}` don't match the statement: missing `name` (did you mean `nmae`?), \
+             unexpected `nmae`, unexpected `email`",
+            std::any::type_name::<Assignee>()
+        )
+    );
+
+    // Prefixes of flattened fields are taken into account.
+    let err = prepare_checked::<Assignment>(
+        &conn,
+        "SELECT 1 AS id, 'x' AS text, 1 AS author_id, 'a' AS author_name, 2 AS editor_id",
+    )
+    .unwrap_err();
+    let PrepareError::Columns(mismatch) = err else {
+        panic!("expected a column mismatch, got {err:?}");
+    };
+    assert_eq!(mismatch.missing, ["editor_name"]);
+
+    // Columns read by position are named after their field, or by their index.
+    let err = prepare_checked::<Stats>(&conn, "SELECT 1").unwrap_err();
+    let PrepareError::Columns(mismatch) = err else {
+        panic!("expected a column mismatch, got {err:?}");
+    };
+    assert_eq!(mismatch.missing, ["max"]);
+
+    let err = prepare_checked::<(Scalar<i64>, Assignee, Scalar<i64>)>(&conn, "SELECT 1, 2 AS id")
+        .unwrap_err();
+    let PrepareError::Columns(mismatch) = err else {
+        panic!("expected a column mismatch, got {err:?}");
+    };
+    assert_eq!(mismatch.missing, ["name", "#3"]);
+
+    assert!(matches!(
+        prepare_checked::<Assignee>(&conn, "SELECT * FROM missing"),
+        Err(PrepareError::Sqlite(_))
+    ));
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[test]
+fn mapper_error() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    let sql = "
+        SELECT
+            1 AS id, 'laundry' AS text,
+            2 AS author_id, 'john' AS author_name, 3 AS author_role_id, NULL AS author_role_kind,
+            4 AS editor_id, 'jack' AS editor_name, NULL AS editor_role_id, NULL AS editor_role_kind";
+
+    let err = conn.query_row(sql, [], Todo::try_from_row).unwrap_err();
+    assert!(matches!(
+        err,
+        rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Null, _)
+    ));
+
+    let mapper_err = MapperError::downcast(&err).expect("Expected a MapperError");
+    assert_eq!(mapper_err.field_path(), "Todo.author.role.kind");
+    assert_eq!(mapper_err.struct_name, "Todo");
+    assert_eq!(mapper_err.fields, ["author", "role", "kind"]);
+    assert_eq!(mapper_err.column.as_deref(), Some("author_role_kind"));
+    assert_eq!(mapper_err.column_index, Some(5));
+    assert_eq!(
+        mapper_err.expected_type,
+        Some(std::any::type_name::<String>())
+    );
+    assert_eq!(mapper_err.storage_class, Some(rusqlite::types::Type::Null));
+    assert!(matches!(
+        mapper_err.source,
+        rusqlite::Error::InvalidColumnType(5, _, rusqlite::types::Type::Null)
+    ));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Conversion error from type Null at index: 5, failed to map `Todo.author.role.kind` \
+             of type `{}` from column `author_role_kind` holding Null: {}",
+            std::any::type_name::<String>(),
+            mapper_err.source
+        )
+    );
+
+    // Works the same inside `query_map` and the typed helpers.
+    let err = conn.query_one_as::<Todo, _>(sql, []).unwrap_err();
+    assert_eq!(
+        MapperError::downcast(&err).map(|err| err.field_path()),
+        Some("Todo.author.role.kind".to_string())
+    );
+
+    // A missing column has no value to report, in a flattened struct as well.
+    let err = conn
+        .query_row(
+            "SELECT 1 AS id, 'a' AS text, 2 AS author_id",
+            [],
+            Assignment::try_from_row,
+        )
+        .unwrap_err();
+    assert!(
+        matches!(&err, rusqlite::Error::InvalidColumnName(name) if name == "author_name"),
+        "{err:?}"
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+fn untitled() -> String {
+    "untitled".to_string()
+}
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Draft {
+    id: i64,
+    #[rusqlite(default_value = "untitled")]
+    title: String,
+    #[rusqlite(default_value)]
+    words: i64,
+    #[rusqlite(default_if_missing)]
+    tags: Option<String>,
+    #[rusqlite(default_if_missing, default_value = "untitled")]
+    subtitle: String,
+}
+
+#[test]
+fn field_defaults() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    let draft: Draft = conn
+        .query_one_as(
+            "SELECT 1 AS id, NULL AS title, NULL AS words, 'a' AS tags, NULL AS subtitle",
+            [],
+        )
+        .unwrap();
+    assert_eq!(
+        draft,
+        Draft {
+            id: 1,
+            title: "untitled".to_string(),
+            words: 0,
+            tags: Some("a".to_string()),
+            subtitle: "untitled".to_string(),
+        }
+    );
+
+    // An old query that doesn't know about `tags` and `subtitle` yet.
+    let sql = "SELECT 2 AS id, 'hello' AS title, 12 AS words";
+    prepare_checked::<Draft>(&conn, sql).expect("Optional columns are not missing");
+    let draft: Draft = conn.query_one_as(sql, []).unwrap();
+    assert_eq!(
+        draft,
+        Draft {
+            id: 2,
+            title: "hello".to_string(),
+            words: 12,
+            tags: None,
+            subtitle: "untitled".to_string(),
+        }
+    );
+    assert_eq!(
+        RowPlan::<Draft>::new(&conn.prepare(sql).unwrap())
+            .unwrap()
+            .indices(),
+        [Some(0), Some(1), Some(2), None, None]
+    );
+
+    // Without `default_value` a null value is still an error.
+    let err = conn
+        .query_one_as::<Draft, _>("SELECT NULL AS id, 'x' AS title, 1 AS words", [])
+        .unwrap_err();
+    assert_eq!(
+        MapperError::downcast(&err).map(|err| err.field_path()),
+        Some("Draft.id".to_string())
+    );
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// Stores a `Duration` as milliseconds.
+mod millis {
+    use std::time::Duration;
+
+    use rusqlite::types::{FromSqlResult, ToSqlOutput, ValueRef};
+
+    pub const fn sqlite_type() -> &'static str {
+        "INTEGER"
+    }
+
+    pub fn from_sql(value: ValueRef<'_>) -> FromSqlResult<Duration> {
+        value
+            .as_i64()
+            .map(|millis| Duration::from_millis(millis as u64))
+    }
+
+    pub fn to_sql(value: &Duration) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(value.as_millis() as i64))
+    }
+}
+
+fn parse_host(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Ipv4Addr> {
+    value
+        .as_str()?
+        .parse()
+        .map_err(|err| rusqlite::types::FromSqlError::Other(Box::new(err)))
+}
+
+fn host_to_sql(value: &Ipv4Addr) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+    Ok(rusqlite::types::ToSqlOutput::from(value.to_string()))
+}
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Job {
+    #[rusqlite(primary_key)]
+    id: i64,
+    #[rusqlite(with = "millis")]
+    timeout: Duration,
+    #[rusqlite(
+        deserialize_with = "parse_host",
+        serialize_with = "host_to_sql",
+        sql_type = "TEXT"
+    )]
+    host: Ipv4Addr,
+    #[rusqlite(with = "millis", default_value)]
+    retry: Duration,
+}
+
+type Label = Option<String>;
+
+fn label_to_sql(value: &Label) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+    Ok(match value {
+        Some(label) => rusqlite::types::ToSqlOutput::from(label.to_uppercase()),
+        None => rusqlite::types::ToSqlOutput::from(rusqlite::types::Null),
+    })
+}
+
+#[derive(ToRow)]
+struct Tag {
+    #[rusqlite(primary_key)]
+    id: i64,
+    // Nullable through the `SqliteTypeInfo` of the alias.
+    #[rusqlite(serialize_with = "label_to_sql")]
+    label: Label,
+}
+
+#[test]
+fn conversion_functions() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    let columns = Job::TABLE.columns;
+    assert_eq!(
+        columns
+            .iter()
+            .map(|column| (column.name, column.sql_type, column.nullable))
+            .collect::<Vec<_>>(),
+        [
+            ("id", "INTEGER", false),
+            ("timeout", "INTEGER", false),
+            ("host", "TEXT", false),
+            ("retry", "INTEGER", false),
+        ]
+    );
+    conn.execute(&Job::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    assert_eq!(
+        Tag::TABLE
+            .columns
+            .iter()
+            .map(|column| (column.name, column.sql_type, column.nullable))
+            .collect::<Vec<_>>(),
+        [("id", "INTEGER", false), ("label", "TEXT", true)]
+    );
+    conn.execute(&Tag::create_table_statement(), ())
+        .expect("Failed to create table");
+    conn.insert(&mut Tag {
+        id: 1,
+        label: Some("urgent".to_string()),
+    })
+    .unwrap();
+    conn.insert(&mut Tag { id: 2, label: None }).unwrap();
+    let labels: Vec<Option<String>> = conn
+        .prepare("SELECT label FROM tag ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(labels, [Some("URGENT".to_string()), None]);
+
+    let mut job = Job {
+        id: 1,
+        timeout: Duration::from_secs(3),
+        host: Ipv4Addr::new(10, 0, 0, 1),
+        retry: Duration::from_millis(250),
+    };
+    conn.insert(&mut job).unwrap();
+
+    let raw: (i64, String) = conn
+        .query_row("SELECT timeout, host FROM job", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(raw, (3000, "10.0.0.1".to_string()));
+    assert_eq!(conn.get::<Job, _>([1]).unwrap(), Some(job));
+
+    let job: Job = conn
+        .query_one_as(
+            "SELECT 2 AS id, 10 AS timeout, '127.0.0.1' AS host, NULL AS retry",
+            [],
+        )
+        .unwrap();
+    assert_eq!(job.retry, Duration::ZERO);
+
+    let err = conn
+        .query_one_as::<Job, _>(
+            "SELECT 3 AS id, 10 AS timeout, 'localhost' AS host, 0 AS retry",
+            [],
+        )
+        .unwrap_err();
+    let err = MapperError::downcast(&err).expect("Wrapped in a MapperError");
+    assert_eq!(err.field_path(), "Job.host");
+    assert!(matches!(
+        err.source,
+        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, _)
+    ));
+
+    let err = conn
+        .query_one_as::<Job, _>(
+            "SELECT 4 AS id, 'x' AS timeout, '::1' AS host, 0 AS retry",
+            [],
+        )
+        .unwrap_err();
+    assert!(matches!(
+        MapperError::downcast(&err).map(|err| &err.source),
+        Some(rusqlite::Error::InvalidColumnType(1, name, _)) if name == "timeout"
+    ));
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StockId(i64);
+
+impl From<i64> for StockId {
+    fn from(value: i64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<StockId> for i64 {
+    fn from(value: StockId) -> Self {
+        value.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Quantity(u64);
+
+impl TryFrom<i64> for Quantity {
+    type Error = rusqlite::types::FromSqlError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        u64::try_from(value)
+            .map(Self)
+            .map_err(|_| rusqlite::types::FromSqlError::OutOfRange(value))
+    }
+}
+
+impl TryFrom<Quantity> for i64 {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(value: Quantity) -> Result<Self, Self::Error> {
+        i64::try_from(value.0)
+    }
+}
+
+#[derive(Debug, PartialEq, FromRow, ToRow)]
+struct Stock {
+    #[rusqlite(primary_key, from = "i64", into = "i64")]
+    id: StockId,
+    #[rusqlite(try_from = "i64", try_into = "i64")]
+    quantity: Quantity,
+}
+
+#[test]
+fn to_row_conversions() {
+    let conn = rusqlite::Connection::open_in_memory().expect("Failed to open in memory database");
+
+    assert_eq!(
+        Stock::TABLE
+            .columns
+            .iter()
+            .map(|column| (column.name, column.sql_type, column.nullable))
+            .collect::<Vec<_>>(),
+        [("id", "INTEGER", false), ("quantity", "INTEGER", false)]
+    );
+    conn.execute(&Stock::create_table_statement(), ())
+        .expect("Failed to create table");
+
+    let mut stock = Stock {
+        id: StockId(1),
+        quantity: Quantity(12),
+    };
+    conn.insert(&mut stock).unwrap();
+    assert_eq!(conn.get::<Stock, _>([1]).unwrap(), Some(stock));
+
+    let stock = Stock {
+        id: StockId(1),
+        quantity: Quantity(30),
+    };
+    assert_eq!(conn.update(&stock).unwrap(), 1);
+    assert_eq!(conn.find_all::<Stock>().unwrap(), [stock]);
+
+    let mut stock = Stock {
+        id: StockId(2),
+        quantity: Quantity(u64::MAX),
+    };
+    let err = conn.insert(&mut stock).unwrap_err();
+    assert!(
+        matches!(&err, rusqlite::Error::ToSqlConversionFailure(err) if err.is::<std::num::TryFromIntError>()),
+        "{err:?}"
+    );
+    assert_eq!(conn.count::<Stock>().unwrap(), 1);
+}